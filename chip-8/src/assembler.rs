@@ -0,0 +1,411 @@
+//! A two-pass assembler for Cowgod-style CHIP-8 source, the same mnemonics the disassembler
+//! prints.
+//!
+//! ```text
+//! define SPEED 2          ; constants; may refer to numbers, labels and other defines
+//! start:  LD I, ball      ; labels end in `:` and may be used before they are defined
+//!         DRW V0, V1, 4
+//!         ADD V0, SPEED
+//!         JP start
+//! ball:   sprite .##..... ; one sprite row per line, `#` or `1` lit, `.` or `0` dark
+//!         sprite ####....
+//!         db 0x60, %01100000, $F0
+//!         dw 0x1234, start
+//! ```
+//!
+//! Numbers are decimal, hex (`0x`, `$`) or binary (`0b`, `%`), and any operand can be a sum
+//! or difference of numbers and symbols. Like labels, defines can be used before they are
+//! defined. Mnemonics, registers and directives are case
+//! insensitive; labels and defines are not.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use crate::constants::{PROGRAM_START, XO_MEMORY_SIZE};
+use crate::instruction::Instruction;
+use crate::symbols::SymbolMap;
+
+/// Operand keywords of `LD` and `ADD`, which symbols would be ambiguous with.
+const KEYWORDS: &[&str] = &["I", "K", "DT", "ST", "F", "HF", "B", "R", "LONG"];
+
+const MNEMONICS: &[&str] = &[
+    "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL", "SE", "SNE",
+    "SAVE", "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN", "SHL", "RND", "DRW",
+    "SKP", "SKNP", "PLANE", "AUDIO", "PITCH",
+];
+
+/// An assembled program, ready for [`crate::Chip8Emulator::new`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Assembly {
+    pub bytes: Vec<u8>,
+    pub symbols: SymbolMap,
+}
+
+/// A problem in the source, tagged with its 1-based line number.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for AssemblyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+/// Assembles `source` for loading at `0x200`, stopping at the first error.
+pub fn assemble(source: &str) -> Result<Assembly, AssemblyError> {
+    let mut assembler = Assembler::default();
+    let mut statements = Vec::new();
+    let mut defines = Vec::new();
+    let mut address = PROGRAM_START;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |message: String| AssemblyError { line: line_number, message };
+
+        let mut rest = line.split(';').next().unwrap_or("").trim();
+        while let Some((label, tail)) = split_label(rest) {
+            assembler.define(label, address as i64).map_err(error)?;
+            assembler.labels.push((label, address as u16));
+            rest = tail.trim_start();
+        }
+        if rest.is_empty() { continue; }
+
+        let (keyword, operands) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let operands = operands.trim();
+
+        let statement = match keyword.to_ascii_lowercase().as_str() {
+            "define" => {
+                let (name, value) = operands.split_once(char::is_whitespace)
+                    .ok_or_else(|| error("expected `define NAME value`".to_string()))?;
+                assembler.reserve(name).map_err(error)?;
+                defines.push((line_number, name, value));
+                continue;
+            }
+            "db" => Statement::Bytes(split_operands(operands)),
+            "dw" => Statement::Words(split_operands(operands)),
+            "sprite" => Statement::Sprite(sprite_row(operands).map_err(error)?),
+            _ => Statement::Instruction(keyword, split_operands(operands)),
+        };
+
+        address += statement.size();
+        if address > XO_MEMORY_SIZE {
+            return Err(error("program does not fit in memory".to_string()));
+        }
+        statements.push((line_number, statement));
+    }
+
+    // Defines may use labels and each other in any order, so resolve whatever can be until
+    // nothing changes. Anything left depends on an undefined symbol, which is the more useful
+    // error, or on itself.
+    while !defines.is_empty() {
+        let pending = defines.len();
+        let mut first_error = None;
+        defines.retain(|&(line, name, value)| match assembler.evaluate(value) {
+            Ok(value) => {
+                assembler.values.insert(name, value);
+                false
+            }
+            Err(message) => {
+                let circular = |e: &AssemblyError| e.message.starts_with("circular");
+                let error = AssemblyError { line, message };
+                if first_error.as_ref().is_none_or(|first| circular(first) && !circular(&error)) {
+                    first_error = Some(error);
+                }
+                true
+            }
+        });
+        if defines.len() == pending {
+            return Err(first_error.unwrap());
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(address - PROGRAM_START);
+    for (line, statement) in statements {
+        assembler.emit(&statement, &mut bytes).map_err(|message| AssemblyError { line, message })?;
+    }
+
+    let mut symbols = SymbolMap::new();
+    for (name, address) in assembler.labels {
+        symbols.insert(name, address);
+    }
+    Ok(Assembly { bytes, symbols })
+}
+
+enum Statement<'a> {
+    Instruction(&'a str, Vec<&'a str>),
+    Bytes(Vec<&'a str>),
+    Words(Vec<&'a str>),
+    Sprite(Vec<u8>),
+}
+
+impl Statement<'_> {
+    fn size(&self) -> usize {
+        match self {
+            Self::Instruction(mnemonic, operands) => if is_long_load(mnemonic, operands) { 4 } else { 2 },
+            Self::Bytes(values) => values.len(),
+            Self::Words(values) => values.len() * 2,
+            Self::Sprite(row) => row.len(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Assembler<'a> {
+    values: HashMap<&'a str, i64>,
+    /// Every symbol name in use, including defines not resolved yet.
+    names: HashSet<&'a str>,
+    labels: Vec<(&'a str, u16)>,
+}
+
+impl<'a> Assembler<'a> {
+    fn define(&mut self, name: &'a str, value: i64) -> Result<(), String> {
+        self.reserve(name)?;
+        self.values.insert(name, value);
+        Ok(())
+    }
+
+    /// Claims `name` for a symbol whose value may only be known later.
+    fn reserve(&mut self, name: &'a str) -> Result<(), String> {
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+        let keyword = KEYWORDS.iter().any(|keyword| keyword.eq_ignore_ascii_case(name));
+        if !valid || keyword || register(name).is_some() {
+            return Err(format!("`{name}` is not a valid symbol name"));
+        }
+        if !self.names.insert(name) {
+            return Err(format!("`{name}` is already defined"));
+        }
+        Ok(())
+    }
+
+    fn emit(&self, statement: &Statement, out: &mut Vec<u8>) -> Result<(), String> {
+        match statement {
+            Statement::Instruction(mnemonic, operands) => {
+                let (opcode, operand) = self.instruction(mnemonic, operands)?.encode();
+                out.extend(opcode.to_be_bytes());
+                if let Some(operand) = operand {
+                    out.extend(operand.to_be_bytes());
+                }
+            }
+            Statement::Bytes(values) => for value in values {
+                out.push(self.ranged(value, -0x80, 0xFF, "byte")? as u8);
+            },
+            Statement::Words(values) => for value in values {
+                out.extend((self.ranged(value, -0x8000, 0xFFFF, "word")? as u16).to_be_bytes());
+            },
+            Statement::Sprite(row) => out.extend(row),
+        }
+        Ok(())
+    }
+
+    fn instruction(&self, mnemonic: &str, operands: &[&str]) -> Result<Instruction, String> {
+        use Instruction::*;
+
+        let upper = mnemonic.to_ascii_uppercase();
+        let instruction = match (upper.as_str(), operands) {
+            ("CLS", []) => ClearScreen,
+            ("RET", []) => Return,
+            ("SCD", [n]) => ScrollDown { n: self.nibble(n)? },
+            ("SCU", [n]) => ScrollUp { n: self.nibble(n)? },
+            ("SCR", []) => ScrollRight,
+            ("SCL", []) => ScrollLeft,
+            ("EXIT", []) => Exit,
+            ("LOW", []) => Lores,
+            ("HIGH", []) => Hires,
+            ("JP", [addr]) => JumpAddr { addr: self.addr(addr)? },
+            ("JP", [v0, addr]) if register(v0) == Some(0) => JumpV0 { addr: self.addr(addr)? },
+            ("CALL", [addr]) => CallAddr { addr: self.addr(addr)? },
+            ("SE", [x, y]) => match register(y) {
+                Some(y) => SkipEqXY { x: reg(x)?, y },
+                None => SkipEqByte { x: reg(x)?, kk: self.byte(y)? },
+            },
+            ("SNE", [x, y]) => match register(y) {
+                Some(y) => SkipNeXY { x: reg(x)?, y },
+                None => SkipNeByte { x: reg(x)?, kk: self.byte(y)? },
+            },
+            ("SAVE", [x, y]) => StoreRange { x: reg(x)?, y: reg(y)? },
+            ("LOAD", [x, y]) => LoadRange { x: reg(x)?, y: reg(y)? },
+            ("LD", [target, source]) => return self.load(target, source),
+            ("ADD", [i, x]) if i.eq_ignore_ascii_case("I") => AddI { x: reg(x)? },
+            ("ADD", [x, y]) => match register(y) {
+                Some(y) => AddXY { x: reg(x)?, y },
+                None => AddByte { x: reg(x)?, kk: self.byte(y)? },
+            },
+            ("OR", [x, y]) => OrXY { x: reg(x)?, y: reg(y)? },
+            ("AND", [x, y]) => AndXY { x: reg(x)?, y: reg(y)? },
+            ("XOR", [x, y]) => XorXY { x: reg(x)?, y: reg(y)? },
+            ("SUB", [x, y]) => SubXY { x: reg(x)?, y: reg(y)? },
+            ("SUBN", [x, y]) => SubnXY { x: reg(x)?, y: reg(y)? },
+            ("SHR", [x]) => ShrXY { x: reg(x)?, y: reg(x)? },
+            ("SHR", [x, y]) => ShrXY { x: reg(x)?, y: reg(y)? },
+            ("SHL", [x]) => ShlXY { x: reg(x)?, y: reg(x)? },
+            ("SHL", [x, y]) => ShlXY { x: reg(x)?, y: reg(y)? },
+            ("RND", [x, kk]) => Random { x: reg(x)?, kk: self.byte(kk)? },
+            ("DRW", [x, y, n]) => Draw { x: reg(x)?, y: reg(y)?, n: self.nibble(n)? },
+            ("SKP", [x]) => SkipKey { x: reg(x)? },
+            ("SKNP", [x]) => SkipNotKey { x: reg(x)? },
+            ("PLANE", [n]) => SelectPlanes { n: self.nibble(n)? },
+            ("AUDIO", []) => LoadAudio,
+            ("PITCH", [x]) => SetPitch { x: reg(x)? },
+            _ if MNEMONICS.contains(&upper.as_str()) => return Err(format!("invalid operands for `{upper}`")),
+            _ => return Err(format!("unknown instruction `{mnemonic}`")),
+        };
+        Ok(instruction)
+    }
+
+    /// The many forms of `LD`.
+    fn load(&self, target: &str, source: &str) -> Result<Instruction, String> {
+        use Instruction::*;
+
+        let keyword = |s: &str, k: &str| s.eq_ignore_ascii_case(k);
+
+        if let Some(x) = register(target) {
+            let instruction = match register(source) {
+                Some(y) => LoadXY { x, y },
+                None if keyword(source, "DT") => LoadDelay { x },
+                None if keyword(source, "K") => WaitKey { x },
+                None if keyword(source, "[I]") => LoadRegisters { x },
+                None if keyword(source, "R") => LoadFlags { x },
+                None => SetByte { x, kk: self.byte(source)? },
+            };
+            return Ok(instruction);
+        }
+
+        if keyword(target, "I") {
+            if let Some(long) = long_operand(source) {
+                return Ok(SetILong { addr: self.ranged(long, 0, 0xFFFF, "address")? as u16 });
+            }
+            return Ok(SetI { addr: self.addr(source)? });
+        }
+
+        let x = reg(source)?;
+        let instruction = match target.to_ascii_uppercase().as_str() {
+            "DT" => SetDelay { x },
+            "ST" => SetSound { x },
+            "F" => FontSprite { x },
+            "HF" => BigFontSprite { x },
+            "B" => StoreBcd { x },
+            "[I]" => StoreRegisters { x },
+            "R" => StoreFlags { x },
+            _ => return Err(format!("cannot load into `{target}`")),
+        };
+        Ok(instruction)
+    }
+
+    fn nibble(&self, expr: &str) -> Result<u8, String> {
+        Ok(self.ranged(expr, 0, 0xF, "nibble")? as u8)
+    }
+
+    fn byte(&self, expr: &str) -> Result<u8, String> {
+        Ok(self.ranged(expr, -0x80, 0xFF, "byte")? as u8)
+    }
+
+    fn addr(&self, expr: &str) -> Result<u16, String> {
+        Ok(self.ranged(expr, 0, 0xFFF, "address")? as u16)
+    }
+
+    fn ranged(&self, expr: &str, min: i64, max: i64, what: &str) -> Result<i64, String> {
+        let value = self.evaluate(expr)?;
+        if value < min || value > max {
+            let article = if what.starts_with(['a', 'e', 'i', 'o', 'u']) { "an" } else { "a" };
+            return Err(format!("{value} does not fit in {article} {what}"));
+        }
+        Ok(value)
+    }
+
+    /// Evaluates a chain of `+` / `-` separated numbers and symbols.
+    fn evaluate(&self, expr: &str) -> Result<i64, String> {
+        let mut total = 0i64;
+        let mut sign = 1;
+        let mut rest = expr.trim();
+        loop {
+            while let Some(tail) = rest.strip_prefix('-') {
+                sign = -sign;
+                rest = tail.trim_start();
+            }
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            total = total.wrapping_add(sign * self.term(rest[..end].trim())?);
+
+            rest = &rest[end..];
+            sign = match rest.chars().next() {
+                None => return Ok(total),
+                Some('+') => 1,
+                Some(_) => -1,
+            };
+            rest = rest[1..].trim_start();
+        }
+    }
+
+    fn term(&self, term: &str) -> Result<i64, String> {
+        let (digits, radix) = if let Some(hex) = term.strip_prefix("0x").or_else(|| term.strip_prefix("0X")).or_else(|| term.strip_prefix('$')) {
+            (hex, 16)
+        } else if let Some(bin) = term.strip_prefix("0b").or_else(|| term.strip_prefix("0B")).or_else(|| term.strip_prefix('%')) {
+            (bin, 2)
+        } else if term.starts_with(|c: char| c.is_ascii_digit()) {
+            (term, 10)
+        } else if term.is_empty() {
+            return Err("missing value".to_string());
+        } else {
+            return match self.values.get(term) {
+                Some(value) => Ok(*value),
+                None if self.names.contains(term) => Err(format!("circular definition of `{term}`")),
+                None => Err(format!("undefined symbol `{term}`")),
+            };
+        };
+        i64::from_str_radix(digits, radix).map_err(|_| format!("invalid number `{term}`"))
+    }
+}
+
+/// Splits a leading `label:` off a line.
+fn split_label(line: &str) -> Option<(&str, &str)> {
+    let (label, rest) = line.split_once(':')?;
+    (!label.is_empty() && !label.contains(char::is_whitespace)).then_some((label, rest))
+}
+
+fn split_operands(operands: &str) -> Vec<&str> {
+    if operands.is_empty() { return Vec::new(); }
+    operands.split(',').map(str::trim).collect()
+}
+
+/// Parses one row of a sprite bitmap, 8 or 16 pixels wide.
+fn sprite_row(row: &str) -> Result<Vec<u8>, String> {
+    let mut bits = 0u16;
+    for c in row.chars() {
+        bits = bits << 1 | match c {
+            '#' | '1' | 'X' | 'x' => 1,
+            '.' | '0' | '_' => 0,
+            _ => return Err(format!("invalid sprite pixel `{c}`")),
+        };
+    }
+    match row.chars().count() {
+        8 => Ok(vec![bits as u8]),
+        16 => Ok(bits.to_be_bytes().to_vec()),
+        n => Err(format!("sprite rows must be 8 or 16 pixels wide, not {n}")),
+    }
+}
+
+fn register(operand: &str) -> Option<u8> {
+    let digit = operand.strip_prefix(['V', 'v'])?;
+    if digit.len() != 1 { return None; }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+fn reg(operand: &str) -> Result<u8, String> {
+    register(operand).ok_or_else(|| format!("expected a register, found `{operand}`"))
+}
+
+/// The address in `LONG nnnn`, as written by the disassembler for `F000 nnnn`.
+fn long_operand(operand: &str) -> Option<&str> {
+    let (keyword, rest) = operand.split_once(char::is_whitespace)?;
+    keyword.eq_ignore_ascii_case("LONG").then_some(rest)
+}
+
+fn is_long_load(mnemonic: &str, operands: &[&str]) -> bool {
+    mnemonic.eq_ignore_ascii_case("LD")
+        && matches!(operands, [i, source] if i.eq_ignore_ascii_case("I") && long_operand(source).is_some())
+}
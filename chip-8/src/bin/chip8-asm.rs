@@ -0,0 +1,39 @@
+//! Assembles Cowgod-style CHIP-8 source into a ROM plus a symbol map for the debugger.
+//!
+//! Usage: chip8-asm <source> [output, default <source>.ch8]
+//!
+//! The symbol map is written next to the output with a `.sym` extension.
+
+use std::path::PathBuf;
+
+use chip_8::assemble;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let Some(source_path) = args.next() else {
+        eprintln!("usage: chip8-asm <source> [output]");
+        std::process::exit(2);
+    };
+    let output = args.next().map(PathBuf::from).unwrap_or_else(|| PathBuf::from(&source_path).with_extension("ch8"));
+
+    let source = match std::fs::read_to_string(&source_path) {
+        Ok(v) => v,
+        Err(e) => { eprintln!("could not read {source_path}: {e}"); std::process::exit(1) },
+    };
+
+    let assembly = match assemble(&source) {
+        Ok(v) => v,
+        Err(e) => { eprintln!("{source_path}:{}: {}", e.line, e.message); std::process::exit(1) },
+    };
+
+    if let Err(e) = std::fs::write(&output, &assembly.bytes) {
+        eprintln!("could not write {}: {e}", output.display());
+        std::process::exit(1);
+    }
+
+    let symbols = output.with_extension("sym");
+    if let Err(e) = std::fs::write(&symbols, assembly.symbols.to_string()) {
+        eprintln!("could not write {}: {e}", symbols.display());
+        std::process::exit(1);
+    }
+}
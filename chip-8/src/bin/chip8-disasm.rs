@@ -0,0 +1,30 @@
+//! Prints a listing of a CHIP-8 ROM.
+//!
+//! Usage: chip8-disasm <rom> [start address, default 0x200]
+
+use chip_8::disassemble_rom;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("usage: chip8-disasm <rom> [start address]");
+        std::process::exit(2);
+    };
+
+    let start = match args.next() {
+        None => 0x200,
+        Some(v) => match u16::from_str_radix(v.trim_start_matches("0x"), 16) {
+            Ok(v) => v,
+            Err(e) => { eprintln!("invalid start address {v}: {e}"); std::process::exit(2) },
+        },
+    };
+
+    let rom = match std::fs::read(&path) {
+        Ok(v) => v,
+        Err(e) => { eprintln!("could not read {path}: {e}"); std::process::exit(1) },
+    };
+
+    for line in disassemble_rom(&rom, start) {
+        println!("{line}");
+    }
+}
@@ -0,0 +1,34 @@
+//! Converts a binary execution trace into one text line per instruction.
+//!
+//! Usage: chip8-trace <trace> [output, default stdout]
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use chip_8::export_text;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("usage: chip8-trace <trace> [output]");
+        std::process::exit(2);
+    };
+
+    let trace = match std::fs::read(&path) {
+        Ok(v) => v,
+        Err(e) => { eprintln!("could not read {path}: {e}"); std::process::exit(1) },
+    };
+
+    let output: Box<dyn Write> = match args.next() {
+        None => Box::new(std::io::stdout().lock()),
+        Some(output) => match File::create(&output) {
+            Ok(v) => Box::new(v),
+            Err(e) => { eprintln!("could not write {output}: {e}"); std::process::exit(1) },
+        },
+    };
+
+    if let Err(e) = export_text(&trace, BufWriter::new(output)) {
+        eprintln!("{path}: {e}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,323 @@
+use std::fmt::Display;
+use std::ops::{Range, RangeInclusive};
+
+use crate::constants::{REGISTER_COUNT, STACK_SIZE};
+use crate::error::{Chip8Error, StepOutcome};
+use crate::Chip8Emulator;
+
+/// The CPU registers at one point in time, cheap enough to copy every instruction.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Registers {
+    pub v: [u8; REGISTER_COUNT],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u8,
+    pub delay: u8,
+    pub sound: u8,
+}
+
+/// A register a [`RegisterCondition`] can watch.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Register {
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    Delay,
+    Sound,
+}
+
+impl Register {
+    pub fn read(self, registers: &Registers) -> u16 {
+        match self {
+            Self::V(x) => registers.v[x as usize & 0x0F] as u16,
+            Self::I => registers.i,
+            Self::Pc => registers.pc,
+            Self::Sp => registers.sp as u16,
+            Self::Delay => registers.delay as u16,
+            Self::Sound => registers.sound as u16,
+        }
+    }
+
+    /// Parses `V0`-`VF`, `I`, `PC`, `SP`, `DT` or `ST`, in any case.
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_ascii_uppercase();
+        Some(match name.as_str() {
+            "I" => Self::I,
+            "PC" => Self::Pc,
+            "SP" => Self::Sp,
+            "DT" => Self::Delay,
+            "ST" => Self::Sound,
+            _ => {
+                let digit = name.strip_prefix('V')?;
+                if digit.len() != 1 { return None; }
+                Self::V(u8::from_str_radix(digit, 16).ok()?)
+            }
+        })
+    }
+}
+
+/// Writes the name [`Register::parse`] accepts, e.g. `V3` or `DT`.
+impl Display for Register {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::V(x) => write!(f, "V{x:X}"),
+            Self::I => write!(f, "I"),
+            Self::Pc => write!(f, "PC"),
+            Self::Sp => write!(f, "SP"),
+            Self::Delay => write!(f, "DT"),
+            Self::Sound => write!(f, "ST"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A comparison of a register against a constant, such as `V3 == 0x10` or `I > 0xF00`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RegisterCondition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl RegisterCondition {
+    pub fn holds(&self, registers: &Registers) -> bool {
+        let current = self.register.read(registers);
+        match self.comparison {
+            Comparison::Eq => current == self.value,
+            Comparison::Ne => current != self.value,
+            Comparison::Lt => current < self.value,
+            Comparison::Le => current <= self.value,
+            Comparison::Gt => current > self.value,
+            Comparison::Ge => current >= self.value,
+        }
+    }
+
+    /// Parses `<register> <op> <value>`, where the operator is one of `==`, `!=`, `<`, `<=`,
+    /// `>`, `>=` and the value is decimal or `0x` hex.
+    pub fn parse(condition: &str) -> Option<Self> {
+        let start = condition.find(['=', '!', '<', '>'])?;
+        let len = condition[start..].find(|c: char| !"=!<>".contains(c)).unwrap_or(condition.len() - start);
+        let comparison = match &condition[start..start + len] {
+            "==" => Comparison::Eq,
+            "!=" => Comparison::Ne,
+            "<" => Comparison::Lt,
+            "<=" => Comparison::Le,
+            ">" => Comparison::Gt,
+            ">=" => Comparison::Ge,
+            _ => return None,
+        };
+
+        let value = condition[start + len..].trim();
+        let value = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+            Some(hex) => u16::from_str_radix(hex, 16).ok()?,
+            None => value.parse().ok()?,
+        };
+
+        Some(Self { register: Register::parse(&condition[..start])?, comparison, value })
+    }
+}
+
+/// A condition that stops [`Chip8Emulator::run_until_break`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Breakpoint {
+    /// Stops when the program counter reaches this address, before that instruction runs.
+    Pc(u16),
+    /// Stops before running any instruction whose opcode matches `value` in the bits set in
+    /// `mask`.
+    Opcode { mask: u16, value: u16 },
+    /// Stops after an instruction reads memory in this range (sprites, `Fx65`, `F002`...).
+    MemoryRead(RangeInclusive<u16>),
+    /// Stops after an instruction writes memory in this range (`Fx33`, `Fx55`, `5xy2`).
+    MemoryWrite(RangeInclusive<u16>),
+    /// Stops after the instruction that makes the condition true. It has to turn false again
+    /// before it can fire a second time.
+    Register(RegisterCondition),
+}
+
+impl Breakpoint {
+    /// Builds an opcode breakpoint from a pattern like `Dxyn` or `Fx0A`: hex digits must
+    /// match, any other character matches any nibble.
+    pub fn opcode_pattern(pattern: &str) -> Option<Self> {
+        if pattern.chars().count() != 4 { return None; }
+
+        let (mut mask, mut value) = (0, 0);
+        for c in pattern.chars() {
+            mask <<= 4;
+            value <<= 4;
+            if let Some(digit) = c.to_digit(16) {
+                mask |= 0xF;
+                value |= digit as u16;
+            }
+        }
+        Some(Self::Opcode { mask, value })
+    }
+
+    /// Whether this breakpoint fires now that an instruction has run, given the registers from
+    /// before it. PC and opcode breakpoints look at the instruction about to run next.
+    fn fires(&self, emulator: &Chip8Emulator, before: &Registers) -> bool {
+        let overlaps = |range: &RangeInclusive<u16>, access: Option<(usize, usize)>| {
+            access.is_some_and(|(start, end)| start <= *range.end() as usize && end > *range.start() as usize)
+        };
+
+        let pc = emulator.program_counter;
+        match self {
+            Self::Pc(address) => pc == *address as usize,
+            Self::Opcode { mask, value } => {
+                let Ok(fetch) = emulator.memory_range(pc, 2) else { return false };
+                let opcode = u16::from_be_bytes([emulator.memory[fetch.start], emulator.memory[fetch.start + 1]]);
+                opcode & mask == *value
+            }
+            Self::MemoryRead(range) => overlaps(range, emulator.last_read),
+            Self::MemoryWrite(range) => overlaps(range, emulator.last_write),
+            Self::Register(condition) => !condition.holds(before) && condition.holds(&emulator.registers()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct BreakpointId(u32);
+
+/// Why [`Chip8Emulator::run_until_break`] returned.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StopReason {
+    Breakpoint(BreakpointId),
+    /// `Fx0A` is waiting for a key.
+    WaitingForKey,
+    /// The program has halted with `00FD`.
+    Halted,
+    /// The instruction budget ran out first.
+    LimitReached,
+    /// The condition given to [`Chip8Emulator::run_until`] became true.
+    Finished,
+}
+
+/// A set of breakpoints and watchpoints, kept outside the emulator so that it stays `Copy`.
+#[derive(Clone, Debug, Default)]
+pub struct Debugger {
+    breakpoints: Vec<(BreakpointId, Breakpoint)>,
+    next_id: u32,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, breakpoint: Breakpoint) -> BreakpointId {
+        let id = BreakpointId(self.next_id);
+        self.next_id += 1;
+        self.breakpoints.push((id, breakpoint));
+        id
+    }
+
+    /// Returns whether the breakpoint existed.
+    pub fn remove(&mut self, id: BreakpointId) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|(existing, _)| *existing != id);
+        self.breakpoints.len() != count
+    }
+
+    pub fn get(&self, id: BreakpointId) -> Option<&Breakpoint> {
+        self.breakpoints.iter().find(|(existing, _)| *existing == id).map(|(_, breakpoint)| breakpoint)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (BreakpointId, &Breakpoint)> {
+        self.breakpoints.iter().map(|(id, breakpoint)| (*id, breakpoint))
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+}
+
+impl Chip8Emulator {
+    /// Snapshot of the CPU registers.
+    pub fn registers(&self) -> Registers {
+        Registers {
+            v: self.v_registers,
+            i: self.i_register,
+            pc: self.program_counter as u16,
+            sp: self.stack_pointer as u8,
+            delay: self.delay_register,
+            sound: self.sound_register,
+        }
+    }
+
+    /// Return addresses currently on the stack, innermost last.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer.min(STACK_SIZE)]
+    }
+
+    /// The addressable memory, fonts and program included.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..self.memory_size()]
+    }
+
+    /// Overwrites one byte of memory, e.g. from a hex editor.
+    pub fn write_memory(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        let target = self.memory_range(addr, 1)?;
+        self.memory[target.start] = value;
+        Ok(())
+    }
+
+    /// Memory read by the last instruction, not counting instruction fetches.
+    pub fn last_memory_read(&self) -> Option<Range<usize>> {
+        self.last_read.map(|(start, end)| start..end)
+    }
+
+    /// Memory written by the last instruction.
+    pub fn last_memory_write(&self) -> Option<Range<usize>> {
+        self.last_write.map(|(start, end)| start..end)
+    }
+
+    /// Runs up to `max_instructions` instructions, stopping early when a breakpoint in
+    /// `debugger` fires or the program blocks. Timers are not ticked.
+    ///
+    /// PC and opcode breakpoints fire when the program counter arrives at a matching
+    /// instruction, leaving it unexecuted; the next call runs it before checking again, so
+    /// resuming never stops on the same breakpoint twice in a row.
+    pub fn run_until_break(&mut self, debugger: &Debugger, max_instructions: usize) -> Result<StopReason, Chip8Error> {
+        self.run_until(debugger, max_instructions, |_| false)
+    }
+
+    /// Like [`Self::run_until_break`], but also stops with [`StopReason::Finished`] once `done`
+    /// returns true for the registers after an instruction. Step Over is
+    /// `run_until(.., |r| r.sp <= sp)` from a call; Step Out the same with `sp - 1`.
+    pub fn run_until(
+        &mut self,
+        debugger: &Debugger,
+        max_instructions: usize,
+        done: impl Fn(&Registers) -> bool,
+    ) -> Result<StopReason, Chip8Error> {
+        for _ in 0..max_instructions {
+            let before = self.registers();
+            match self.tick()? {
+                StepOutcome::Executed => {}
+                StepOutcome::WaitingForKey => return Ok(StopReason::WaitingForKey),
+                StepOutcome::Halted => return Ok(StopReason::Halted),
+            }
+
+            if let Some((id, _)) = debugger.breakpoints.iter().find(|(_, b)| b.fires(self, &before)) {
+                return Ok(StopReason::Breakpoint(*id));
+            }
+            if done(&self.registers()) {
+                return Ok(StopReason::Finished);
+            }
+        }
+        Ok(StopReason::LimitReached)
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt::Display;
+
+use crate::instruction::Instruction;
+
+/// Decodes the instruction starting with `opcode`, `next` being the word after it as in
+/// [`Instruction::decode`]. Unknown words become [`Instruction::Data`].
+pub fn disassemble(opcode: u16, next: u16) -> Instruction {
+    Instruction::decode(opcode, next).unwrap_or(Instruction::Data(opcode))
+}
+
+/// Renders Cowgod-style mnemonics, e.g. `LD V3, 0x1F`, `DRW V0, V1, 5`, `SKP VA`.
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            ScrollDown { n } => write!(f, "SCD {n}"),
+            ScrollUp { n } => write!(f, "SCU {n}"),
+            ClearScreen => write!(f, "CLS"),
+            Return => write!(f, "RET"),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            Lores => write!(f, "LOW"),
+            Hires => write!(f, "HIGH"),
+            JumpAddr { addr } => write!(f, "JP 0x{addr:03X}"),
+            CallAddr { addr } => write!(f, "CALL 0x{addr:03X}"),
+            SkipEqByte { x, kk } => write!(f, "SE V{x:X}, 0x{kk:02X}"),
+            SkipNeByte { x, kk } => write!(f, "SNE V{x:X}, 0x{kk:02X}"),
+            SkipEqXY { x, y } => write!(f, "SE V{x:X}, V{y:X}"),
+            StoreRange { x, y } => write!(f, "SAVE V{x:X}, V{y:X}"),
+            LoadRange { x, y } => write!(f, "LOAD V{x:X}, V{y:X}"),
+            SetByte { x, kk } => write!(f, "LD V{x:X}, 0x{kk:02X}"),
+            AddByte { x, kk } => write!(f, "ADD V{x:X}, 0x{kk:02X}"),
+            LoadXY { x, y } => write!(f, "LD V{x:X}, V{y:X}"),
+            OrXY { x, y } => write!(f, "OR V{x:X}, V{y:X}"),
+            AndXY { x, y } => write!(f, "AND V{x:X}, V{y:X}"),
+            XorXY { x, y } => write!(f, "XOR V{x:X}, V{y:X}"),
+            AddXY { x, y } => write!(f, "ADD V{x:X}, V{y:X}"),
+            SubXY { x, y } => write!(f, "SUB V{x:X}, V{y:X}"),
+            ShrXY { x, y } => write!(f, "SHR V{x:X}, V{y:X}"),
+            SubnXY { x, y } => write!(f, "SUBN V{x:X}, V{y:X}"),
+            ShlXY { x, y } => write!(f, "SHL V{x:X}, V{y:X}"),
+            SkipNeXY { x, y } => write!(f, "SNE V{x:X}, V{y:X}"),
+            SetI { addr } => write!(f, "LD I, 0x{addr:03X}"),
+            JumpV0 { addr } => write!(f, "JP V0, 0x{addr:03X}"),
+            Random { x, kk } => write!(f, "RND V{x:X}, 0x{kk:02X}"),
+            Draw { x, y, n } => write!(f, "DRW V{x:X}, V{y:X}, {n}"),
+            SkipKey { x } => write!(f, "SKP V{x:X}"),
+            SkipNotKey { x } => write!(f, "SKNP V{x:X}"),
+            SetILong { addr } => write!(f, "LD I, LONG 0x{addr:04X}"),
+            SelectPlanes { n } => write!(f, "PLANE {n}"),
+            LoadAudio => write!(f, "AUDIO"),
+            LoadDelay { x } => write!(f, "LD V{x:X}, DT"),
+            WaitKey { x } => write!(f, "LD V{x:X}, K"),
+            SetDelay { x } => write!(f, "LD DT, V{x:X}"),
+            SetSound { x } => write!(f, "LD ST, V{x:X}"),
+            AddI { x } => write!(f, "ADD I, V{x:X}"),
+            FontSprite { x } => write!(f, "LD F, V{x:X}"),
+            BigFontSprite { x } => write!(f, "LD HF, V{x:X}"),
+            StoreBcd { x } => write!(f, "LD B, V{x:X}"),
+            SetPitch { x } => write!(f, "PITCH V{x:X}"),
+            StoreRegisters { x } => write!(f, "LD [I], V{x:X}"),
+            LoadRegisters { x } => write!(f, "LD V{x:X}, [I]"),
+            StoreFlags { x } => write!(f, "LD R, V{x:X}"),
+            LoadFlags { x } => write!(f, "LD V{x:X}, R"),
+            Data(word) => write!(f, "DW 0x{word:04X}"),
+        }
+    }
+}
+
+/// One line of a ROM listing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DisassembledLine {
+    pub address: u16,
+    pub opcode: u16,
+    /// The second word of `F000 nnnn`.
+    pub operand: Option<u16>,
+    pub instruction: Instruction,
+}
+
+impl Display for DisassembledLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.operand {
+            Some(operand) => write!(f, "0x{:03X}: {:04X} {:04X}  {}", self.address, self.opcode, operand, self.instruction),
+            None => write!(f, "0x{:03X}: {:04X}       {}", self.address, self.opcode, self.instruction),
+        }
+    }
+}
+
+/// Disassembles a whole ROM loaded at `start`, two bytes at a time. Data mixed into the code
+/// shows up as whatever it happens to decode to, since a linear sweep cannot tell them apart.
+pub fn disassemble_rom(rom: &[u8], start: u16) -> Vec<DisassembledLine> {
+    let word_at = |offset: usize| -> Option<u16> {
+        let high = *rom.get(offset)?;
+        Some(((high as u16) << 8) | *rom.get(offset + 1).unwrap_or(&0) as u16)
+    };
+
+    let mut lines = Vec::with_capacity(rom.len() / 2 + 1);
+    let mut offset = 0;
+    while let Some(opcode) = word_at(offset) {
+        let operand = word_at(offset + 2);
+        let instruction = disassemble(opcode, operand.unwrap_or(0));
+        let operand = match instruction {
+            Instruction::SetILong { .. } => operand,
+            _ => None,
+        };
+
+        lines.push(DisassembledLine {
+            address: start.wrapping_add(offset as u16),
+            opcode,
+            operand,
+            instruction,
+        });
+        offset += if operand.is_some() { 4 } else { 2 };
+    }
+    lines
+}
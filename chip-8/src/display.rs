@@ -1,5 +1,6 @@
 use std::fmt::Display;
 use crate::Chip8Emulator;
+use crate::disassembler::disassemble;
 
 impl Display for Chip8Emulator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -34,9 +35,13 @@ impl Display for Chip8Emulator {
         )?;
         writeln!(f, "Keys: [{}]", keys)?;
         writeln!(f, "Program Counter: 0x{:X} | Stack Pointer: 0x{:X}", self.program_counter, self.stack_pointer)?;
+        if let Some(bytes) = self.memory.get(self.program_counter..self.program_counter + 2) {
+            let next = self.memory.get(self.program_counter + 2..self.program_counter + 4).map_or(0, |b| u16::from_be_bytes([b[0], b[1]]));
+            writeln!(f, "Next Instruction: {}", disassemble(u16::from_be_bytes([bytes[0], bytes[1]]), next))?;
+        }
         writeln!(f, "Stack: [{stack}]")?;
         writeln!(f, "Memory:\n{}", config.hexdump_bytes(self.memory))?;
-        writeln!(f, "Display:\n{}", config.hexdump_bytes(self.display_ram))?;
+        write!(f, "Display:\n{}", self.framebuffer)?;
 
         Ok(())
     }
@@ -0,0 +1,71 @@
+use std::fmt::Display;
+
+/// A fault raised while loading or executing a program.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Chip8Error {
+    /// `2nnn` was executed with every stack slot already in use.
+    StackOverflow { pc: u16 },
+    /// `00EE` was executed with an empty stack.
+    StackUnderflow { pc: u16 },
+    /// An instruction or fetch touched memory past the end of the address space.
+    MemoryOutOfBounds { addr: usize },
+    /// The word at `pc` does not decode to any known instruction.
+    UnknownOpcode { pc: u16, opcode: u16 },
+    /// The program does not fit between its load address and the end of memory.
+    RomTooLarge { size: usize, max: usize },
+    /// The program contains no bytes.
+    EmptyRom,
+    /// Reading the program, or writing a trace, failed.
+    Io(std::io::ErrorKind),
+    /// A save state is truncated, corrupt or not a save state at all.
+    InvalidSaveState,
+    /// A save state was written by an incompatible version of the format.
+    UnsupportedSaveStateVersion(u8),
+    /// A binary trace is truncated or not a trace at all.
+    InvalidTrace,
+    /// A movie file is truncated, corrupt or not a movie at all.
+    InvalidMovie,
+    /// A movie was started with a different ROM than it was recorded with.
+    MovieRomMismatch,
+    /// Movie playback ended in a different state than the recording, as state hashes.
+    MovieDesync { expected: u64, actual: u64 },
+}
+
+impl Display for Chip8Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StackOverflow { pc } => write!(f, "stack overflow at 0x{pc:03X}"),
+            Self::StackUnderflow { pc } => write!(f, "stack underflow at 0x{pc:03X}"),
+            Self::MemoryOutOfBounds { addr } => write!(f, "memory access out of bounds at 0x{addr:X}"),
+            Self::UnknownOpcode { pc, opcode } => write!(f, "unknown opcode 0x{opcode:04X} at 0x{pc:03X}"),
+            Self::RomTooLarge { size, max } => write!(f, "ROM is {size} bytes, at most {max} fit in memory"),
+            Self::EmptyRom => write!(f, "ROM is empty"),
+            Self::Io(kind) => write!(f, "I/O error: {kind}"),
+            Self::InvalidSaveState => write!(f, "save state is corrupt"),
+            Self::UnsupportedSaveStateVersion(v) => write!(f, "save state format version {v} is not supported"),
+            Self::InvalidTrace => write!(f, "trace is corrupt"),
+            Self::InvalidMovie => write!(f, "movie is corrupt"),
+            Self::MovieRomMismatch => write!(f, "movie was recorded with a different ROM"),
+            Self::MovieDesync { expected, actual } => write!(f, "movie playback desynced: state hash {actual:016X}, expected {expected:016X}"),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+impl From<std::io::Error> for Chip8Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value.kind())
+    }
+}
+
+/// What happened during a single call to [`crate::Chip8Emulator::tick`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StepOutcome {
+    /// An instruction ran to completion.
+    Executed,
+    /// `Fx0A` is blocking until a key is pressed; the same instruction runs again next tick.
+    WaitingForKey,
+    /// `00FD` has stopped the program; further ticks do nothing.
+    Halted,
+}
@@ -0,0 +1,200 @@
+use std::fmt::Display;
+
+use crate::constants::*;
+
+/// Number of XO-CHIP bit planes.
+pub const PLANE_COUNT: usize = 2;
+
+/// Characters [`Framebuffer`]'s `Display` uses for each plane combination.
+const PIXEL_CHARS: [char; 4] = ['.', '#', '+', '@'];
+
+/// The part of the screen changed since a renderer last asked, in pixels.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct DirtyRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DirtyRect {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+
+    /// The smallest rectangle covering both.
+    pub fn union(self, other: Self) -> Self {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        Self {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+}
+
+/// One display row: a bit-packed line per plane, the leftmost pixel in the most significant bit.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Row {
+    planes: [u128; PLANE_COUNT],
+    width: usize,
+}
+
+impl Row {
+    /// The bits of `plane` (0 or 1), only the top [`Framebuffer::width`] of which are used.
+    pub fn plane(&self, plane: usize) -> u128 {
+        self.planes[plane]
+    }
+
+    /// The planes lit at `x` as a bitmask, see [`Framebuffer::pixel`].
+    pub fn pixel(&self, x: usize) -> u8 {
+        let bit = 1 << (127 - x);
+        self.planes.iter().enumerate().fold(0, |pixel, (i, plane)| pixel | (((plane & bit != 0) as u8) << i))
+    }
+
+    /// Every pixel of the row from left to right.
+    pub fn pixels(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..self.width).map(|x| self.pixel(x))
+    }
+}
+
+/// The display at its current resolution, 64x32 or 128x64.
+///
+/// Drawing, clearing and scrolling record the area they touched; renderers collect it with
+/// [`Self::take_dirty`] and only redraw that.
+#[derive(Copy, Clone, Debug)]
+pub struct Framebuffer {
+    pub(crate) planes: [[u128; HIRES_DISPLAY_HEIGHT]; PLANE_COUNT],
+    width: usize,
+    height: usize,
+    dirty: Option<DirtyRect>,
+}
+
+impl Framebuffer {
+    /// A blank screen, all of it dirty so the first render draws everything.
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        Self {
+            planes: [[0; HIRES_DISPLAY_HEIGHT]; PLANE_COUNT],
+            width,
+            height,
+            dirty: Some(DirtyRect { x: 0, y: 0, width, height }),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The planes lit at (`x`, `y`) as a bitmask: bit 0 for the first plane, bit 1 for the
+    /// XO-CHIP second plane, giving four colors.
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.row(y).pixel(x)
+    }
+
+    pub fn row(&self, y: usize) -> Row {
+        Row { planes: [self.planes[0][y], self.planes[1][y]], width: self.width }
+    }
+
+    /// The rows from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = Row> + '_ {
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    /// The area changed since the last call to [`Self::take_dirty`], if any.
+    pub fn dirty(&self) -> Option<DirtyRect> {
+        self.dirty
+    }
+
+    /// Returns the changed area and marks the screen clean.
+    pub fn take_dirty(&mut self) -> Option<DirtyRect> {
+        self.dirty.take()
+    }
+
+    fn mark_dirty(&mut self, rect: DirtyRect) {
+        self.dirty = Some(self.dirty.map_or(rect, |dirty| dirty.union(rect)));
+    }
+
+    fn mark_all_dirty(&mut self) {
+        self.mark_dirty(DirtyRect { x: 0, y: 0, width: self.width, height: self.height });
+    }
+
+    /// Bits of a row that are on screen at the current width.
+    fn row_mask(&self) -> u128 {
+        !u128::MAX.checked_shr(self.width as u32).unwrap_or(0)
+    }
+
+    /// Switches to a `width` x `height` screen, blank when `clear` is set. Otherwise the
+    /// pixels stay where they are and whatever falls outside the new size is hidden.
+    pub(crate) fn resize(&mut self, width: usize, height: usize, clear: bool) {
+        if clear {
+            self.planes = [[0; HIRES_DISPLAY_HEIGHT]; PLANE_COUNT];
+        }
+        self.width = width;
+        self.height = height;
+        self.dirty = Some(DirtyRect { x: 0, y: 0, width, height });
+    }
+
+    /// Clears the planes in `planes`.
+    pub(crate) fn clear(&mut self, planes: u8) {
+        for (i, plane) in self.planes.iter_mut().enumerate() {
+            if planes & (1 << i) != 0 {
+                *plane = [0; HIRES_DISPLAY_HEIGHT];
+            }
+        }
+        self.mark_all_dirty();
+    }
+
+    /// XORs `plane` (a single plane bit) at (`x`, `y`), returning whether it was lit before.
+    pub(crate) fn toggle(&mut self, x: usize, y: usize, plane: u8) -> bool {
+        let bit = 1 << (127 - x);
+        let row = &mut self.planes[plane.trailing_zeros() as usize][y];
+        let was_lit = *row & bit != 0;
+        *row ^= bit;
+        self.mark_dirty(DirtyRect { x, y, width: 1, height: 1 });
+        was_lit
+    }
+
+    /// Moves the selected planes `dx` pixels right and `dy` pixels down; pixels scrolled in
+    /// from outside the screen are blank.
+    pub(crate) fn scroll(&mut self, dx: isize, dy: isize, planes: u8) {
+        let (height, mask) = (self.height as isize, self.row_mask());
+        for (i, plane) in self.planes.iter_mut().enumerate() {
+            if planes & (1 << i) == 0 { continue; }
+
+            let source = *plane;
+            for y in 0..height {
+                let source_y = y - dy;
+                let row = if (0..height).contains(&source_y) { source[source_y as usize] } else { 0 };
+                let shifted = if dx >= 0 { row >> dx } else { row << -dx };
+                plane[y as usize] = shifted & mask;
+            }
+        }
+        self.mark_all_dirty();
+    }
+}
+
+/// Two framebuffers are equal when they show the same picture, whatever is left to redraw.
+impl PartialEq for Framebuffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.height == other.height && self.rows().eq(other.rows())
+    }
+}
+
+impl Eq for Framebuffer {}
+
+/// One text line per row: `.` for an unlit pixel, `#` for the first plane, `+` for the
+/// second and `@` for both.
+impl Display for Framebuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.rows() {
+            let line: String = row.pixels().map(|pixel| PIXEL_CHARS[pixel as usize]).collect();
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
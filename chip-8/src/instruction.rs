@@ -0,0 +1,257 @@
+use crate::macros::mask;
+
+/// A decoded CHIP-8, SUPER-CHIP or XO-CHIP instruction.
+///
+/// Register operands (`x`, `y`) are register indices, `kk` an immediate byte, `n` a nibble
+/// and `addr` a 12-bit address.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Instruction {
+    /// 00Cn
+    ScrollDown { n: u8 },
+    /// 00Dn
+    ScrollUp { n: u8 },
+    /// 00E0
+    ClearScreen,
+    /// 00EE
+    Return,
+    /// 00FB
+    ScrollRight,
+    /// 00FC
+    ScrollLeft,
+    /// 00FD
+    Exit,
+    /// 00FE
+    Lores,
+    /// 00FF
+    Hires,
+    /// 1nnn
+    JumpAddr { addr: u16 },
+    /// 2nnn
+    CallAddr { addr: u16 },
+    /// 3xkk
+    SkipEqByte { x: u8, kk: u8 },
+    /// 4xkk
+    SkipNeByte { x: u8, kk: u8 },
+    /// 5xy0
+    SkipEqXY { x: u8, y: u8 },
+    /// 5xy2
+    StoreRange { x: u8, y: u8 },
+    /// 5xy3
+    LoadRange { x: u8, y: u8 },
+    /// 6xkk
+    SetByte { x: u8, kk: u8 },
+    /// 7xkk
+    AddByte { x: u8, kk: u8 },
+    /// 8xy0
+    LoadXY { x: u8, y: u8 },
+    /// 8xy1
+    OrXY { x: u8, y: u8 },
+    /// 8xy2
+    AndXY { x: u8, y: u8 },
+    /// 8xy3
+    XorXY { x: u8, y: u8 },
+    /// 8xy4
+    AddXY { x: u8, y: u8 },
+    /// 8xy5
+    SubXY { x: u8, y: u8 },
+    /// 8xy6
+    ShrXY { x: u8, y: u8 },
+    /// 8xy7
+    SubnXY { x: u8, y: u8 },
+    /// 8xyE
+    ShlXY { x: u8, y: u8 },
+    /// 9xy0
+    SkipNeXY { x: u8, y: u8 },
+    /// Annn
+    SetI { addr: u16 },
+    /// Bnnn
+    JumpV0 { addr: u16 },
+    /// Cxkk
+    Random { x: u8, kk: u8 },
+    /// Dxyn
+    Draw { x: u8, y: u8, n: u8 },
+    /// Ex9E
+    SkipKey { x: u8 },
+    /// ExA1
+    SkipNotKey { x: u8 },
+    /// F000 nnnn, the only instruction two words long.
+    SetILong { addr: u16 },
+    /// Fn01
+    SelectPlanes { n: u8 },
+    /// F002
+    LoadAudio,
+    /// Fx07
+    LoadDelay { x: u8 },
+    /// Fx0A
+    WaitKey { x: u8 },
+    /// Fx15
+    SetDelay { x: u8 },
+    /// Fx18
+    SetSound { x: u8 },
+    /// Fx1E
+    AddI { x: u8 },
+    /// Fx29
+    FontSprite { x: u8 },
+    /// Fx30
+    BigFontSprite { x: u8 },
+    /// Fx33
+    StoreBcd { x: u8 },
+    /// Fx3A
+    SetPitch { x: u8 },
+    /// Fx55
+    StoreRegisters { x: u8 },
+    /// Fx65
+    LoadRegisters { x: u8 },
+    /// Fx75
+    StoreFlags { x: u8 },
+    /// Fx85
+    LoadFlags { x: u8 },
+    /// A word that is not a known instruction, usually sprite or other data. Only produced by
+    /// the disassembler; executing it fails with [`crate::Chip8Error::UnknownOpcode`].
+    Data(u16),
+}
+
+impl Instruction {
+    /// Decodes the instruction starting with `opcode`, or `None` if it is not a known
+    /// instruction. `next` is the word after it, which only `F000 nnnn` reads.
+    pub fn decode(opcode: u16, next: u16) -> Option<Self> {
+        use Instruction::*;
+
+        let (o, x, y, n) = mask!(opcode, 0, 1, 2, 3);
+        let (kk, addr) = mask!(opcode, 23, 123);
+        let (x, y, n) = (x as u8, y as u8, n as u8);
+
+        Some(match (o, x, y, n) {
+            (0x0, 0x0, 0xC, _) => ScrollDown { n },
+            (0x0, 0x0, 0xD, _) => ScrollUp { n },
+            (0x0, 0x0, 0xE, 0x0) => ClearScreen,
+            (0x0, 0x0, 0xE, 0xE) => Return,
+            (0x0, 0x0, 0xF, 0xB) => ScrollRight,
+            (0x0, 0x0, 0xF, 0xC) => ScrollLeft,
+            (0x0, 0x0, 0xF, 0xD) => Exit,
+            (0x0, 0x0, 0xF, 0xE) => Lores,
+            (0x0, 0x0, 0xF, 0xF) => Hires,
+            (0x1, ..) => JumpAddr { addr },
+            (0x2, ..) => CallAddr { addr },
+            (0x3, ..) => SkipEqByte { x, kk },
+            (0x4, ..) => SkipNeByte { x, kk },
+            (0x5, _, _, 0x0) => SkipEqXY { x, y },
+            (0x5, _, _, 0x2) => StoreRange { x, y },
+            (0x5, _, _, 0x3) => LoadRange { x, y },
+            (0x6, ..) => SetByte { x, kk },
+            (0x7, ..) => AddByte { x, kk },
+            (0x8, _, _, 0x0) => LoadXY { x, y },
+            (0x8, _, _, 0x1) => OrXY { x, y },
+            (0x8, _, _, 0x2) => AndXY { x, y },
+            (0x8, _, _, 0x3) => XorXY { x, y },
+            (0x8, _, _, 0x4) => AddXY { x, y },
+            (0x8, _, _, 0x5) => SubXY { x, y },
+            (0x8, _, _, 0x6) => ShrXY { x, y },
+            (0x8, _, _, 0x7) => SubnXY { x, y },
+            (0x8, _, _, 0xE) => ShlXY { x, y },
+            (0x9, _, _, 0x0) => SkipNeXY { x, y },
+            (0xA, ..) => SetI { addr },
+            (0xB, ..) => JumpV0 { addr },
+            (0xC, ..) => Random { x, kk },
+            (0xD, ..) => Draw { x, y, n },
+            (0xE, _, 0x9, 0xE) => SkipKey { x },
+            (0xE, _, 0xA, 0x1) => SkipNotKey { x },
+            (0xF, 0x0, 0x0, 0x0) => SetILong { addr: next },
+            (0xF, _, 0x0, 0x1) => SelectPlanes { n: x },
+            (0xF, 0x0, 0x0, 0x2) => LoadAudio,
+            (0xF, _, 0x0, 0x7) => LoadDelay { x },
+            (0xF, _, 0x0, 0xA) => WaitKey { x },
+            (0xF, _, 0x1, 0x5) => SetDelay { x },
+            (0xF, _, 0x1, 0x8) => SetSound { x },
+            (0xF, _, 0x1, 0xE) => AddI { x },
+            (0xF, _, 0x2, 0x9) => FontSprite { x },
+            (0xF, _, 0x3, 0x0) => BigFontSprite { x },
+            (0xF, _, 0x3, 0x3) => StoreBcd { x },
+            (0xF, _, 0x3, 0xA) => SetPitch { x },
+            (0xF, _, 0x5, 0x5) => StoreRegisters { x },
+            (0xF, _, 0x6, 0x5) => LoadRegisters { x },
+            (0xF, _, 0x7, 0x5) => StoreFlags { x },
+            (0xF, _, 0x8, 0x5) => LoadFlags { x },
+            _ => return None,
+        })
+    }
+
+    /// Length in bytes: 4 for `F000 nnnn`, 2 for everything else.
+    pub fn size(self) -> usize {
+        match self {
+            Self::SetILong { .. } => 4,
+            _ => 2,
+        }
+    }
+
+    /// Encodes back to the opcode and, for `F000 nnnn`, the word after it: the inverse of
+    /// [`Self::decode`]. Operands are masked to their field width.
+    pub fn encode(self) -> (u16, Option<u16>) {
+        match self {
+            Self::SetILong { addr } => (0xF000, Some(addr)),
+            _ => (self.opcode(), None),
+        }
+    }
+
+    fn opcode(self) -> u16 {
+        use Instruction::*;
+
+        let xy = |base: u16, x: u8, y: u8, n: u16| base | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | n;
+        let xkk = |base: u16, x: u8, kk: u8| base | (x as u16 & 0xF) << 8 | kk as u16;
+        let fx = |x: u8, kk: u16| 0xF000 | (x as u16 & 0xF) << 8 | kk;
+
+        match self {
+            ScrollDown { n } => 0x00C0 | (n as u16 & 0xF),
+            ScrollUp { n } => 0x00D0 | (n as u16 & 0xF),
+            ClearScreen => 0x00E0,
+            Return => 0x00EE,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            Lores => 0x00FE,
+            Hires => 0x00FF,
+            JumpAddr { addr } => 0x1000 | (addr & 0xFFF),
+            CallAddr { addr } => 0x2000 | (addr & 0xFFF),
+            SkipEqByte { x, kk } => xkk(0x3000, x, kk),
+            SkipNeByte { x, kk } => xkk(0x4000, x, kk),
+            SkipEqXY { x, y } => xy(0x5000, x, y, 0x0),
+            StoreRange { x, y } => xy(0x5000, x, y, 0x2),
+            LoadRange { x, y } => xy(0x5000, x, y, 0x3),
+            SetByte { x, kk } => xkk(0x6000, x, kk),
+            AddByte { x, kk } => xkk(0x7000, x, kk),
+            LoadXY { x, y } => xy(0x8000, x, y, 0x0),
+            OrXY { x, y } => xy(0x8000, x, y, 0x1),
+            AndXY { x, y } => xy(0x8000, x, y, 0x2),
+            XorXY { x, y } => xy(0x8000, x, y, 0x3),
+            AddXY { x, y } => xy(0x8000, x, y, 0x4),
+            SubXY { x, y } => xy(0x8000, x, y, 0x5),
+            ShrXY { x, y } => xy(0x8000, x, y, 0x6),
+            SubnXY { x, y } => xy(0x8000, x, y, 0x7),
+            ShlXY { x, y } => xy(0x8000, x, y, 0xE),
+            SkipNeXY { x, y } => xy(0x9000, x, y, 0x0),
+            SetI { addr } => 0xA000 | (addr & 0xFFF),
+            JumpV0 { addr } => 0xB000 | (addr & 0xFFF),
+            Random { x, kk } => xkk(0xC000, x, kk),
+            Draw { x, y, n } => xy(0xD000, x, y, n as u16 & 0xF),
+            SkipKey { x } => xkk(0xE000, x, 0x9E),
+            SkipNotKey { x } => xkk(0xE000, x, 0xA1),
+            SetILong { .. } => 0xF000,
+            SelectPlanes { n } => fx(n, 0x01),
+            LoadAudio => 0xF002,
+            LoadDelay { x } => fx(x, 0x07),
+            WaitKey { x } => fx(x, 0x0A),
+            SetDelay { x } => fx(x, 0x15),
+            SetSound { x } => fx(x, 0x18),
+            AddI { x } => fx(x, 0x1E),
+            FontSprite { x } => fx(x, 0x29),
+            BigFontSprite { x } => fx(x, 0x30),
+            StoreBcd { x } => fx(x, 0x33),
+            SetPitch { x } => fx(x, 0x3A),
+            StoreRegisters { x } => fx(x, 0x55),
+            LoadRegisters { x } => fx(x, 0x65),
+            StoreFlags { x } => fx(x, 0x75),
+            LoadFlags { x } => fx(x, 0x85),
+            Data(word) => word,
+        }
+    }
+}
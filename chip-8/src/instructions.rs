@@ -1,244 +1,435 @@
+use std::ops::Range;
+
 use crate::constants::*;
 use crate::Chip8Emulator;
-use crate::macros::mask;
+use crate::error::Chip8Error;
+use crate::quirks::IndexIncrement;
 
 impl Chip8Emulator {
     // 0x00E0
-    pub(crate) fn clear_screen(&mut self, _instruction: u16) {
-        self.display_ram = [0; DISPLAY_WIDTH * DISPLAY_HEIGHT + 1];
+    pub(crate) fn clear_screen(&mut self) -> Result<(), Chip8Error> {
+        self.framebuffer.clear(self.selected_planes);
+        Ok(())
     }
     // 0x00EE
-    pub(crate) fn return_from_subroutine(&mut self, _instruction: u16) {
+    pub(crate) fn return_from_subroutine(&mut self) -> Result<(), Chip8Error> {
+        if self.stack_pointer == 0 {
+            return Err(Chip8Error::StackUnderflow { pc: self.current_instruction_address() });
+        }
         self.stack_pointer -= 1;
         self.program_counter = self.stack[self.stack_pointer] as usize;
+        Ok(())
+    }
+    // 0x00Cn
+    pub(crate) fn scroll_down(&mut self, n: usize) -> Result<(), Chip8Error> {
+        self.framebuffer.scroll(0, n as isize, self.selected_planes);
+        Ok(())
+    }
+    // 0x00Dn
+    pub(crate) fn scroll_up(&mut self, n: usize) -> Result<(), Chip8Error> {
+        self.framebuffer.scroll(0, -(n as isize), self.selected_planes);
+        Ok(())
+    }
+    // 0x00FB
+    pub(crate) fn scroll_right(&mut self) -> Result<(), Chip8Error> {
+        self.framebuffer.scroll(4, 0, self.selected_planes);
+        Ok(())
+    }
+    // 0x00FC
+    pub(crate) fn scroll_left(&mut self) -> Result<(), Chip8Error> {
+        self.framebuffer.scroll(-4, 0, self.selected_planes);
+        Ok(())
+    }
+    // 0x00FD
+    pub(crate) fn exit(&mut self) -> Result<(), Chip8Error> {
+        self.halted = true;
+        Ok(())
+    }
+    // 0x00FE
+    pub(crate) fn lores(&mut self) -> Result<(), Chip8Error> {
+        self.framebuffer.resize(DISPLAY_WIDTH, DISPLAY_HEIGHT, self.quirks.clear_on_resolution_change);
+        Ok(())
+    }
+    // 0x00FF
+    pub(crate) fn hires(&mut self) -> Result<(), Chip8Error> {
+        self.framebuffer.resize(HIRES_DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT, self.quirks.clear_on_resolution_change);
+        Ok(())
     }
 
     // 0x1nnn
-    pub(crate) fn jmp_addr(&mut self, instruction: u16) {
-        let addr = mask!(instruction, 123);
+    pub(crate) fn jmp_addr(&mut self, addr: u16) -> Result<(), Chip8Error> {
         self.program_counter = addr as usize;
+        Ok(())
     }
 
     // 0x2nnn
-    pub(crate) fn call_addr(&mut self, _instruction: u16) {
+    pub(crate) fn call_addr(&mut self, addr: u16) -> Result<(), Chip8Error> {
+        if self.stack_pointer >= STACK_SIZE {
+            return Err(Chip8Error::StackOverflow { pc: self.current_instruction_address() });
+        }
         self.stack[self.stack_pointer] = self.program_counter as u16;
         self.stack_pointer += 1;
+        self.program_counter = addr as usize;
+        Ok(())
     }
 
     // 0x3xkk
-    pub(crate) fn skip_register_eq(&mut self, instruction: u16) {
-        let (x, kk) = mask!(instruction, 1, 23);
+    pub(crate) fn skip_register_eq(&mut self, x: usize, kk: u8) -> Result<(), Chip8Error> {
         if self.v_registers[x] == kk {
-            self.program_counter += 2;
+            self.skip_next_instruction();
         }
+        Ok(())
     }
 
     // 0x4xkk
-    pub(crate) fn skip_register_ne(&mut self, instruction: u16) {
-        let (x, kk) = mask!(instruction, 1, 23);
+    pub(crate) fn skip_register_ne(&mut self, x: usize, kk: u8) -> Result<(), Chip8Error> {
         if self.v_registers[x] != kk {
-            self.program_counter += 2;
+            self.skip_next_instruction();
         }
+        Ok(())
     }
 
     // 0x5xy0
-    pub(crate) fn skip_registers_eq(&mut self, instruction: u16) {
-        let (x, y) = mask!(instruction, 1, 2);
+    pub(crate) fn skip_registers_eq(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         if self.v_registers[x] == self.v_registers[y] {
-            self.program_counter += 2;
+            self.skip_next_instruction();
+        }
+        Ok(())
+    }
+    // 0x5xy2
+    pub(crate) fn store_register_range(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        let target = self.write_range(self.i_register as usize, x.abs_diff(y) + 1)?;
+        for (offset, register) in register_range(x, y).enumerate() {
+            self.memory[target.start + offset] = self.v_registers[register];
         }
+        Ok(())
+    }
+    // 0x5xy3
+    pub(crate) fn load_register_range(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        let source = self.read_range(self.i_register as usize, x.abs_diff(y) + 1)?;
+        for (offset, register) in register_range(x, y).enumerate() {
+            self.v_registers[register] = self.memory[source.start + offset];
+        }
+        Ok(())
     }
 
     // 0x6xkk
-    pub(crate) fn set_register(&mut self, instruction: u16) {
-        let (x, kk) = mask!(instruction, 1, 23);
+    pub(crate) fn set_register(&mut self, x: usize, kk: u8) -> Result<(), Chip8Error> {
         self.v_registers[x] = kk;
+        Ok(())
     }
 
     // 0x7xkk
-    pub(crate) fn add_register(&mut self, instruction: u16) {
-        let (x, kk) = mask!(instruction, 1, 23);
+    pub(crate) fn add_register(&mut self, x: usize, kk: u8) -> Result<(), Chip8Error> {
         let (v, _) = self.v_registers[x].overflowing_add(kk);
         self.v_registers[x] = v;
+        Ok(())
     }
 
     // 0x8xy0
-    pub(crate) fn load_xy(&mut self, instruction: u16) {
-        let (x, y) = mask!(instruction, 1, 2);
+    pub(crate) fn load_xy(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         self.v_registers[x] = self.v_registers[y];
+        Ok(())
     }
     // 0x8xy1
-    pub(crate) fn or_xy(&mut self, instruction: u16) {
-        let (x, y) = mask!(instruction, 1, 2);
+    pub(crate) fn or_xy(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         self.v_registers[x] |= self.v_registers[y];
+        if self.quirks.vf_reset { self.v_registers[0xF] = 0; }
+        Ok(())
     }
     // 0x8xy2
-    pub(crate) fn and_xy(&mut self, instruction: u16) {
-        let (x, y) = mask!(instruction, 1, 2);
+    pub(crate) fn and_xy(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         self.v_registers[x] &= self.v_registers[y];
+        if self.quirks.vf_reset { self.v_registers[0xF] = 0; }
+        Ok(())
     }
     // 0x8xy3
-    pub(crate) fn xor_xy(&mut self, instruction: u16) {
-        let (x, y) = mask!(instruction, 1, 2);
+    pub(crate) fn xor_xy(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         self.v_registers[x] ^= self.v_registers[y];
+        if self.quirks.vf_reset { self.v_registers[0xF] = 0; }
+        Ok(())
     }
     // 0x8xy4
-    pub(crate) fn add_xy(&mut self, instruction: u16) {
-        let (x, y) = mask!(instruction, 1, 2);
+    // The flag is written after the result, so with x = F it is VF that survives.
+    pub(crate) fn add_xy(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         let (v, overflow) = self.v_registers[x].overflowing_add(self.v_registers[y]);
-        self.v_registers[0xF] = if overflow { 1 } else { 0 };
         self.v_registers[x] = v;
+        self.v_registers[0xF] = overflow as u8;
+        Ok(())
     }
     // 0x8xy5
-    pub(crate) fn sub_xy(&mut self, instruction: u16) {
-        let (x, y) = mask!(instruction, 1, 2);
-        let (v, _) = self.v_registers[x].overflowing_sub(self.v_registers[y]);
-        self.v_registers[0xF] = if self.v_registers[x] > self.v_registers[y] { 1 } else { 0 };
+    pub(crate) fn sub_xy(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        let (v, borrow) = self.v_registers[x].overflowing_sub(self.v_registers[y]);
         self.v_registers[x] = v;
+        self.v_registers[0xF] = !borrow as u8;
+        Ok(())
     }
     // 0x8xy6
-    pub(crate) fn shr_xy(&mut self, instruction: u16) {
-        let x = mask!(1, instruction);
-        self.v_registers[0xF] = self.v_registers[x] & 0x01;
-        self.v_registers[x] >>= 1;
+    pub(crate) fn shr_xy(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        let source = if self.quirks.shift_uses_vy { self.v_registers[y] } else { self.v_registers[x] };
+        self.v_registers[x] = source >> 1;
+        self.v_registers[0xF] = source & 0x01;
+        Ok(())
     }
     // 0x8xy7
-    pub(crate) fn subn_xy(&mut self, instruction: u16) {
-        let (x, y) = mask!(instruction, 1, 2);
-        let (v, _) = self.v_registers[x].overflowing_sub(self.v_registers[y]);
-        self.v_registers[0xF] = if self.v_registers[x] < self.v_registers[y] { 1 } else { 0 };
+    pub(crate) fn subn_xy(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        let (v, borrow) = self.v_registers[y].overflowing_sub(self.v_registers[x]);
         self.v_registers[x] = v;
+        self.v_registers[0xF] = !borrow as u8;
+        Ok(())
     }
     // 0x8xyE
-    pub(crate) fn shl_xy(&mut self, instruction: u16) {
-        let x = mask!(1, instruction);
-        self.v_registers[0xF] = if (self.v_registers[x] & 0x80) > 0 { 1 } else { 0 };
-        self.v_registers[x] <<= 1;
+    pub(crate) fn shl_xy(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        let source = if self.quirks.shift_uses_vy { self.v_registers[y] } else { self.v_registers[x] };
+        self.v_registers[x] = source << 1;
+        self.v_registers[0xF] = (source & 0x80) >> 7;
+        Ok(())
     }
 
     // 0x9xy0
-    pub(crate) fn skip_registers_ne(&mut self, instruction: u16) {
-        let (x, y) = mask!(instruction, 1, 2);
+    pub(crate) fn skip_registers_ne(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         if self.v_registers[x] != self.v_registers[y] {
-            self.program_counter += 2;
+            self.skip_next_instruction();
         }
+        Ok(())
     }
 
     // 0xAnnn
-    pub(crate) fn set_i_register(&mut self, instruction: u16) {
-        let addr = mask!(123, instruction);
+    pub(crate) fn set_i_register(&mut self, addr: u16) -> Result<(), Chip8Error> {
         self.i_register = addr;
+        Ok(())
     }
 
     // 0xBnnn
-    pub(crate) fn jmp_v0(&mut self, instruction: u16) {
-        let addr = mask!(123, instruction);
-        self.program_counter = self.v_registers[0x0] as usize + addr as usize;
+    pub(crate) fn jmp_v0(&mut self, addr: u16) -> Result<(), Chip8Error> {
+        let x = (addr >> 8) as usize & 0x0F;
+        let offset = if self.quirks.jump_uses_vx { self.v_registers[x] } else { self.v_registers[0x0] };
+        self.program_counter = offset as usize + addr as usize;
+        Ok(())
     }
 
     // 0xCxkk
-    pub(crate) fn rand_byte(&mut self, instruction: u16) {
-        let (x, kk) = mask!(instruction, 1, 23);
-        self.v_registers[x] = rand::random::<u8>() & kk;
+    pub(crate) fn rand_byte(&mut self, x: usize, kk: u8) -> Result<(), Chip8Error> {
+        self.v_registers[x] = self.rng.next_byte() & kk;
+        Ok(())
     }
 
-    // 0xDxyn
-    pub(crate) fn draw_sprite(&mut self, instruction: u16) {
-        let (x, y, n) = mask!(instruction, 1, 2, 3);
-        let x_pos = self.v_registers[x] as usize % DISPLAY_WIDTH;
-        let y_pos = self.v_registers[y] as usize % DISPLAY_HEIGHT;
+    // 0xDxyn, 0xDxy0 draws a 16x16 sprite
+    // With both XO-CHIP planes selected, the sprite data for the first plane is followed by the second's.
+    // The starting position always wraps; pixels past the edge clip or wrap per axis as the quirks say.
+    pub(crate) fn draw_sprite(&mut self, x: usize, y: usize, n: usize) -> Result<(), Chip8Error> {
+        let (width, height) = (self.display_width(), self.display_height());
+        let (sprite_width, rows) = if n == 0 { (16, 16) } else { (8, n) };
+        let bytes_per_row = sprite_width / 8;
+        let plane_bytes = rows * bytes_per_row;
 
-        self.v_registers[0xF] = 0;
+        let x_pos = self.v_registers[x] as usize % width;
+        let y_pos = self.v_registers[y] as usize % height;
+        let planes = self.selected_planes;
+        let mut sprite = [0; 2 * 32];
+        let sprite = self.read_sprite(&mut sprite[..plane_bytes * planes.count_ones() as usize]);
 
-        for row in 0..n {
-            let byte = self.memory[self.i_register as usize + row];
+        self.v_registers[0xF] = 0;
 
-            for col in 0..8 {
-                let mut index = (y_pos + row) * DISPLAY_WIDTH + (x_pos + col);
-                if index >= 2048 { index = 2047 };
+        let mut plane_rows = sprite.chunks(bytes_per_row);
+        for plane in [0b01, 0b10] {
+            if planes & plane == 0 { continue; }
 
-                let pixel = byte & (0x80 >> col);
-                let screen_pixel = &mut self.display_ram[index];
+            for (row, data) in plane_rows.by_ref().take(rows).enumerate() {
+                let Some(y_row) = wrap_or_clip(y_pos + row, height, self.quirks.wrap_sprites_y) else { continue };
+                let bits = (data[0] as u16) << 8 | *data.get(1).unwrap_or(&0) as u16;
 
-                if pixel > 0 {
-                    if *screen_pixel == 0xFF {
+                for col in 0..sprite_width {
+                    let Some(x_col) = wrap_or_clip(x_pos + col, width, self.quirks.wrap_sprites_x) else { break };
+                    if bits & (0x8000 >> col) != 0 && self.framebuffer.toggle(x_col, y_row, plane) {
                         self.v_registers[0xF] = 1;
                     }
-
-                    *screen_pixel ^= 0xFF;
                 }
             }
         }
+        Ok(())
+    }
+
+    /// Fills `sprite` from memory at I, wrapping around to address 0 at the end of memory as
+    /// the address bus would. Only the part before the wrap is recorded for read watchpoints.
+    fn read_sprite<'a>(&mut self, sprite: &'a mut [u8]) -> &'a [u8] {
+        let size = self.memory_size();
+        let start = self.i_register as usize % size;
+        for (i, byte) in sprite.iter_mut().enumerate() {
+            *byte = self.memory[(start + i) % size];
+        }
+        self.last_read = Some((start, (start + sprite.len()).min(size)));
+        sprite
     }
 
     // 0xEx9E
-    pub(crate) fn skip_vx_key(&mut self, instruction: u16) {
-        let x = mask!(1, instruction);
-        if self.key_flags[self.v_registers[x] as usize] {
-            self.program_counter += 2;
+    pub(crate) fn skip_vx_key(&mut self, x: usize) -> Result<(), Chip8Error> {
+        if self.key_flags[self.v_registers[x] as usize & 0x0F] {
+            self.skip_next_instruction();
         }
+        Ok(())
     }
     // 0xExA1
-    pub(crate) fn nskip_vx_key(&mut self, instruction: u16) {
-        let x = mask!(1, instruction);
-        if !self.key_flags[self.v_registers[x] as usize] {
-            self.program_counter += 2;
+    pub(crate) fn nskip_vx_key(&mut self, x: usize) -> Result<(), Chip8Error> {
+        if !self.key_flags[self.v_registers[x] as usize & 0x0F] {
+            self.skip_next_instruction();
         }
+        Ok(())
     }
 
+    // 0xF000 nnnn
+    pub(crate) fn set_i_register_long(&mut self, addr: u16) -> Result<(), Chip8Error> {
+        self.i_register = addr;
+        Ok(())
+    }
+    // 0xFn01
+    pub(crate) fn select_planes(&mut self, n: usize) -> Result<(), Chip8Error> {
+        self.selected_planes = n as u8 & 0b11;
+        Ok(())
+    }
+    // 0xF002
+    pub(crate) fn load_audio_pattern(&mut self) -> Result<(), Chip8Error> {
+        let source = self.read_range(self.i_register as usize, AUDIO_PATTERN_SIZE)?;
+        self.audio_pattern.copy_from_slice(&self.memory[source]);
+        Ok(())
+    }
     // 0xFx07
-    pub(crate) fn load_delay(&mut self, instruction: u16) {
-        let x = mask!(1, instruction);
+    pub(crate) fn load_delay(&mut self, x: usize) -> Result<(), Chip8Error> {
         self.v_registers[x] = self.delay_register;
+        Ok(())
     }
     // 0xFx0A
-    pub(crate) fn wait_key(&mut self, instruction: u16) {
-        let x = mask!(1, instruction);
-        let result = self.key_flags.iter().enumerate().find(|(_, v)| **v);
-        if let Some((i, _)) = result {
-            self.v_registers[x] = i as u8;
-        } else { self.program_counter -= 2; }
+    // Only key events from after the wait started count: the next press, or the next release
+    // under `wait_key_release`, so one keystroke never satisfies two waits.
+    pub(crate) fn wait_key(&mut self, x: usize) -> Result<(), Chip8Error> {
+        if let Some(key) = self.key_event.take() {
+            self.v_registers[x] = key;
+            self.waiting_for_key = false;
+        } else {
+            self.program_counter -= 2;
+            self.waiting_for_key = true;
+        }
+        Ok(())
     }
     // 0xFx15
-    pub(crate) fn set_delay(&mut self, instruction: u16) {
-        let x = mask!(1, instruction);
+    pub(crate) fn set_delay(&mut self, x: usize) -> Result<(), Chip8Error> {
         self.delay_register = self.v_registers[x];
+        Ok(())
     }
     // 0xFx18
-    pub(crate) fn set_sound(&mut self, instruction: u16) {
-        let x = mask!(1, instruction);
+    pub(crate) fn set_sound(&mut self, x: usize) -> Result<(), Chip8Error> {
         self.sound_register = self.v_registers[x];
+        Ok(())
     }
     // 0xFx1E
-    pub(crate) fn set_add_i_register(&mut self, instruction: u16) {
-        let x = mask!(1, instruction);
-        self.i_register += self.v_registers[x] as u16;
+    pub(crate) fn set_add_i_register(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.i_register = self.i_register.wrapping_add(self.v_registers[x] as u16);
+        Ok(())
     }
     // 0xFx29
-    pub(crate) fn set_sprite_location(&mut self, instruction: u16) {
-        let x = mask!(1, instruction);
-        self.i_register = self.v_registers[x] as u16 * 5;
+    pub(crate) fn set_sprite_location(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.i_register = (self.v_registers[x] & 0x0F) as u16 * 5;
+        Ok(())
+    }
+    // 0xFx30
+    pub(crate) fn set_big_sprite_location(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.i_register = (BIG_FONT_START + (self.v_registers[x] & 0x0F) as usize * 10) as u16;
+        Ok(())
+    }
+    // 0xFx3A
+    pub(crate) fn set_pitch(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.pitch = self.v_registers[x];
+        Ok(())
     }
     // 0xFx33
-    pub(crate) fn store_bcd(&mut self, instruction: u16) {
-        let x = mask!(1, instruction);
+    pub(crate) fn store_bcd(&mut self, x: usize) -> Result<(), Chip8Error> {
+        let digits = self.write_range(self.i_register as usize, 3)?;
         let mut value = self.v_registers[x];
-        self.memory[self.i_register as usize + 2] = value % 10;
+        self.memory[digits.start + 2] = value % 10;
         value /= 10;
-        self.memory[self.i_register as usize + 1] = value % 10;
+        self.memory[digits.start + 1] = value % 10;
         value /= 10;
-        self.memory[self.i_register as usize] = value % 10;
+        self.memory[digits.start] = value % 10;
+        Ok(())
     }
     // 0xFx55
-    pub(crate) fn store_registers(&mut self, _instruction: u16) {
-        for (i, register) in self.v_registers.iter().enumerate() {
-            self.memory[self.i_register as usize + i] = *register;
-        }
+    pub(crate) fn store_registers(&mut self, x: usize) -> Result<(), Chip8Error> {
+        let target = self.write_range(self.i_register as usize, x + 1)?;
+        self.memory[target].copy_from_slice(&self.v_registers[..=x]);
+        self.increment_i_after_transfer(x);
+        Ok(())
     }
     // 0xFx65
-    pub(crate) fn load_registers(&mut self, _instruction: u16) {
-        for (i, register) in self.v_registers.iter_mut().enumerate() {
-            *register = self.memory[self.i_register as usize + i];
+    pub(crate) fn load_registers(&mut self, x: usize) -> Result<(), Chip8Error> {
+        let source = self.read_range(self.i_register as usize, x + 1)?;
+        self.v_registers[..=x].copy_from_slice(&self.memory[source]);
+        self.increment_i_after_transfer(x);
+        Ok(())
+    }
+
+    // 0xFx75
+    pub(crate) fn store_rpl_flags(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.rpl_flags[..=x].copy_from_slice(&self.v_registers[..=x]);
+        Ok(())
+    }
+    // 0xFx85
+    pub(crate) fn load_rpl_flags(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.v_registers[..=x].copy_from_slice(&self.rpl_flags[..=x]);
+        Ok(())
+    }
+
+    fn increment_i_after_transfer(&mut self, x: usize) {
+        self.i_register = self.i_register.wrapping_add(match self.quirks.index_increment {
+            IndexIncrement::XPlusOne => x as u16 + 1,
+            IndexIncrement::X => x as u16,
+            IndexIncrement::Unchanged => 0,
+        });
+    }
+
+    /// Skips the instruction at the program counter, stepping over both words of `F000 nnnn`.
+    fn skip_next_instruction(&mut self) {
+        let next = self.program_counter;
+        let is_long = next + 1 < self.memory_size() && self.memory[next] == 0xF0 && self.memory[next + 1] == 0x00;
+        self.program_counter += if is_long { 4 } else { 2 };
+    }
+
+    /// Address of the instruction currently executing (the program counter has already moved past it).
+    pub(crate) fn current_instruction_address(&self) -> u16 {
+        self.program_counter.wrapping_sub(2) as u16
+    }
+
+    /// Bounds-checks memory an instruction reads, recording it for read watchpoints.
+    fn read_range(&mut self, addr: usize, len: usize) -> Result<Range<usize>, Chip8Error> {
+        let range = self.memory_range(addr, len)?;
+        self.last_read = Some((range.start, range.end));
+        Ok(range)
+    }
+
+    /// Bounds-checks memory an instruction writes, recording it for write watchpoints.
+    fn write_range(&mut self, addr: usize, len: usize) -> Result<Range<usize>, Chip8Error> {
+        let range = self.memory_range(addr, len)?;
+        self.last_write = Some((range.start, range.end));
+        Ok(range)
+    }
+
+    /// Bounds-checks `len` bytes of memory starting at `addr`.
+    pub(crate) fn memory_range(&self, addr: usize, len: usize) -> Result<Range<usize>, Chip8Error> {
+        let size = self.memory_size();
+        if addr + len > size {
+            return Err(Chip8Error::MemoryOutOfBounds { addr: addr.max(size) });
         }
+        Ok(addr..addr + len)
     }
 }
+
+/// `position` on an axis `length` pixels long, wrapped around or `None` when it's off screen
+/// and the axis clips.
+fn wrap_or_clip(position: usize, length: usize, wrap: bool) -> Option<usize> {
+    if position < length { Some(position) } else if wrap { Some(position % length) } else { None }
+}
+
+/// Register indices from x to y inclusive, in either direction.
+fn register_range(x: usize, y: usize) -> impl Iterator<Item = usize> {
+    let ascending = x <= y;
+    (0..=x.abs_diff(y)).map(move |i| if ascending { x + i } else { x - i })
+}
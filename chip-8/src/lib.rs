@@ -1,10 +1,41 @@
+mod assembler;
 mod constants;
+mod debugger;
+mod disassembler;
 mod display;
+mod error;
+mod framebuffer;
+mod instruction;
 mod instructions;
-mod instruction_table;
+mod movie;
+mod octo;
+mod quirks;
+mod rewind;
+mod rng;
+mod savestate;
+mod symbols;
+mod trace;
+
+use std::io::Read;
+use std::path::Path;
 
 use constants::*;
 
+pub use assembler::{assemble, Assembly, AssemblyError};
+pub use debugger::{Breakpoint, BreakpointId, Comparison, Debugger, Register, RegisterCondition, Registers, StopReason};
+pub use disassembler::{disassemble, disassemble_rom, DisassembledLine};
+pub use error::{Chip8Error, StepOutcome};
+pub use framebuffer::{DirtyRect, Framebuffer, Row, PLANE_COUNT};
+pub use instruction::Instruction;
+pub use movie::Movie;
+pub use octo::{compile_octo, OctoTarget};
+pub use quirks::{IndexIncrement, Quirks};
+pub use rewind::RewindBuffer;
+pub use rng::{RandomSource, Rng, SplitMix, DEFAULT_SEED};
+pub use savestate::SAVE_STATE_VERSION;
+pub use symbols::SymbolMap;
+pub use trace::{export_text, read_trace, TraceFilter, TraceFormat, TraceRecord, Tracer};
+
 mod macros {
     macro_rules! mask {
         ($in:ident, $($v:tt),*) => {
@@ -39,55 +70,366 @@ mod macros {
 
 #[derive(Copy, Clone, Debug)]
 pub struct Chip8Emulator {
-    pub(crate) memory: [u8; MEMORY_SIZE],
-    pub display_ram: [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT + 1],
+    pub(crate) memory: [u8; XO_MEMORY_SIZE],
+    pub(crate) framebuffer: Framebuffer,
+    pub(crate) selected_planes: u8,
 
     pub(crate) v_registers: [u8; REGISTER_COUNT],
     pub(crate) i_register: u16,
     pub(crate) delay_register: u8,
     pub(crate) sound_register: u8,
+    pub(crate) audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pub(crate) pitch: u8,
 
     pub(crate) program_counter: usize,
     pub(crate) stack_pointer: usize,
 
     pub(crate) stack: [u16; STACK_SIZE],
+    pub(crate) rpl_flags: [u8; RPL_FLAG_COUNT],
+
+    pub(crate) key_flags: [bool; KEY_COUNT],
+    pub(crate) waiting_for_key: bool,
+    /// The key whose press or release, depending on [`Quirks::wait_key_release`], ends the
+    /// current `Fx0A` wait.
+    pub(crate) key_event: Option<u8>,
+    pub(crate) halted: bool,
+    /// Instructions executed since the program was loaded.
+    pub(crate) cycles: u64,
 
-    pub key_flags: [bool; KEY_COUNT],
+    pub(crate) quirks: Quirks,
+    pub(crate) rng: Rng,
+
+    /// Memory read and written by the last instruction, as half-open address ranges.
+    pub(crate) last_read: Option<(usize, usize)>,
+    pub(crate) last_write: Option<(usize, usize)>,
 }
 
 impl Chip8Emulator {
     pub fn new(program: &[u8]) -> Self {
-        let mut memory = [0; MEMORY_SIZE];
+        Self::with_quirks(program, Quirks::default())
+    }
+
+    /// Panics if `program` does not fit in memory; see [`Self::try_new`] for a fallible version.
+    pub fn with_quirks(program: &[u8], quirks: Quirks) -> Self {
+        let mut emulator = Self::blank(quirks);
+        if let Err(e) = emulator.write_program(program, PROGRAM_START) {
+            panic!("{e}");
+        }
+        emulator
+    }
+
+    pub fn try_new(program: &[u8]) -> Result<Self, Chip8Error> {
+        let mut emulator = Self::default();
+        emulator.load_rom(program)?;
+        Ok(emulator)
+    }
+
+    pub fn from_reader(mut reader: impl Read) -> Result<Self, Chip8Error> {
+        let mut program = Vec::new();
+        reader.read_to_end(&mut program)?;
+        Self::try_new(&program)
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Chip8Error> {
+        Self::try_new(&std::fs::read(path)?)
+    }
+
+    /// Resets the machine and loads `program` at the standard start address (0x200).
+    /// Quirks, RPL flags and the random number generator are kept.
+    pub fn load_rom(&mut self, program: &[u8]) -> Result<(), Chip8Error> {
+        self.load_rom_at(program, PROGRAM_START)
+    }
+
+    /// Resets the machine and loads `program` at `start`, which also becomes the initial
+    /// program counter (e.g. 0x600 for ETI-660 programs). Quirks, RPL flags and the random
+    /// number generator are kept.
+    pub fn load_rom_at(&mut self, program: &[u8], start: usize) -> Result<(), Chip8Error> {
+        if program.is_empty() {
+            return Err(Chip8Error::EmptyRom);
+        }
+
+        let mut emulator = Self::blank(self.quirks);
+        emulator.write_program(program, start)?;
+        emulator.rpl_flags = self.rpl_flags;
+        emulator.rng = self.rng;
+        *self = emulator;
+        Ok(())
+    }
+
+    fn write_program(&mut self, program: &[u8], start: usize) -> Result<(), Chip8Error> {
+        let max = self.memory_size().saturating_sub(start);
+        if program.len() > max {
+            return Err(Chip8Error::RomTooLarge { size: program.len(), max });
+        }
+
+        self.memory[start..start + program.len()].copy_from_slice(program);
+        self.program_counter = start;
+        Ok(())
+    }
+
+    fn blank(quirks: Quirks) -> Self {
+        let mut memory = [0; XO_MEMORY_SIZE];
 
         memory[0..FONT_BOOK.len()].copy_from_slice(FONT_BOOK.as_slice());
-        memory[PROGRAM_START..PROGRAM_START+program.len()].copy_from_slice(program);
+        memory[BIG_FONT_START..BIG_FONT_START + BIG_FONT_BOOK.len()].copy_from_slice(BIG_FONT_BOOK.as_slice());
 
         Self {
             memory,
-            display_ram: [0xFF; DISPLAY_WIDTH * DISPLAY_HEIGHT + 1],
+            framebuffer: Framebuffer::new(DISPLAY_WIDTH, DISPLAY_HEIGHT),
+            selected_planes: 0b01,
             v_registers: [0; REGISTER_COUNT],
             i_register: 0,
             delay_register: 0,
             sound_register: 0,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH,
             program_counter: PROGRAM_START,
             stack_pointer: 0,
             stack: [0; STACK_SIZE],
+            rpl_flags: [0; RPL_FLAG_COUNT],
             key_flags: [false; KEY_COUNT],
+            waiting_for_key: false,
+            key_event: None,
+            halted: false,
+            cycles: 0,
+            quirks,
+            rng: Rng::default(),
+            last_read: None,
+            last_write: None,
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn rng(&self) -> Rng {
+        self.rng
+    }
+
+    /// Replaces the generator behind `Cxkk`, e.g. `set_rng(Rng::seeded(42))` for a
+    /// reproducible run.
+    pub fn set_rng(&mut self, rng: Rng) {
+        self.rng = rng;
+    }
+
+    /// Width of the active display mode: 64 in lo-res, 128 in SUPER-CHIP hi-res.
+    pub fn display_width(&self) -> usize {
+        self.framebuffer.width()
+    }
+
+    /// Height of the active display mode: 32 in lo-res, 64 in SUPER-CHIP hi-res.
+    pub fn display_height(&self) -> usize {
+        self.framebuffer.height()
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    /// The part of the screen changed since the last call, see [`Framebuffer::take_dirty`].
+    pub fn take_dirty(&mut self) -> Option<DirtyRect> {
+        self.framebuffer.take_dirty()
+    }
+
+    /// Addressable memory for the current quirks profile.
+    pub fn memory_size(&self) -> usize {
+        self.quirks.memory_size.min(XO_MEMORY_SIZE)
+    }
+
+    /// The XO-CHIP audio pattern buffer loaded by `F002`: 128 one-bit samples, MSB first.
+    pub fn audio_pattern(&self) -> [u8; AUDIO_PATTERN_SIZE] {
+        self.audio_pattern
+    }
+
+    /// Playback rate of the audio pattern in samples per second, set by `Fx3A`.
+    pub fn audio_sample_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    /// Holds down a keypad key (0-F). Pressing a key that is already down does nothing.
+    pub fn press_key(&mut self, key: u8) {
+        let key = key & 0x0F;
+        if self.key_flags[key as usize] { return }
+
+        self.key_flags[key as usize] = true;
+        if self.waiting_for_key && !self.quirks.wait_key_release && self.key_event.is_none() {
+            self.key_event = Some(key);
+        }
+    }
+
+    /// Lets go of a keypad key (0-F). Releasing a key that is already up does nothing.
+    pub fn release_key(&mut self, key: u8) {
+        let key = key & 0x0F;
+        if !self.key_flags[key as usize] { return }
+
+        self.key_flags[key as usize] = false;
+        if self.waiting_for_key && self.quirks.wait_key_release && self.key_event.is_none() {
+            self.key_event = Some(key);
         }
     }
 
-    pub fn tick(&mut self) {
-        let i_first = self.memory[self.program_counter];
-        let i_second = self.memory[self.program_counter + 1];
+    /// Presses or releases `key` to match `pressed`.
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if pressed { self.press_key(key) } else { self.release_key(key) }
+    }
+
+    pub fn is_key_pressed(&self, key: u8) -> bool {
+        self.key_flags[key as usize & 0x0F]
+    }
 
-        let instruction = ((i_first as u16) << 8) + (i_second as u16);
-        let opcode = ((instruction & 0xF000) >> 12) as usize;
+    /// The held keys as a mask, bit n for key n.
+    pub fn key_mask(&self) -> u16 {
+        self.key_flags.iter().enumerate().fold(0, |keys, (i, pressed)| keys | ((*pressed as u16) << i))
+    }
 
-        self.program_counter += 2;
-        instruction_table::MAIN_INSTRUCTION_TABLE[opcode].resolve(self, instruction);
+    /// Presses and releases keys to match `keys`, bit n for key n, in key order.
+    pub fn set_key_mask(&mut self, keys: u16) {
+        for key in 0..KEY_COUNT as u8 {
+            self.set_key(key, keys & 1 << key != 0);
+        }
+    }
+
+    /// Whether the buzzer should sound, i.e. the sound timer is still counting down.
+    pub fn is_sound_active(&self) -> bool {
+        self.sound_register > 0
+    }
+
+    /// Instructions executed since the program was loaded, `Fx0A` polls included.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
 
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// SUPER-CHIP RPL user flags (`Fx75` / `Fx85`). They survive [`Self::load_rom`], and can be
+    /// read out and restored here to persist them between sessions.
+    pub fn rpl_flags(&self) -> [u8; RPL_FLAG_COUNT] {
+        self.rpl_flags
+    }
+
+    pub fn set_rpl_flags(&mut self, flags: [u8; RPL_FLAG_COUNT]) {
+        self.rpl_flags = flags;
+    }
+
+    pub fn tick(&mut self) -> Result<StepOutcome, Chip8Error> {
+        if self.halted { return Ok(StepOutcome::Halted); }
+        self.last_read = None;
+        self.last_write = None;
+
+        let word_at = |emulator: &Self, addr: usize| -> Result<u16, Chip8Error> {
+            let fetch = emulator.memory_range(addr, 2)?;
+            Ok(u16::from_be_bytes([emulator.memory[fetch.start], emulator.memory[fetch.start + 1]]))
+        };
+        let opcode = word_at(self, self.program_counter)?;
+        // Only F000 reads the second word, so only it can fault on one past the end of memory
+        let next = word_at(self, self.program_counter + 2);
+        let instruction = Instruction::decode(opcode, *next.as_ref().unwrap_or(&0))
+            .ok_or(Chip8Error::UnknownOpcode { pc: self.program_counter as u16, opcode })?;
+        if instruction.size() == 4 {
+            next?;
+        }
+
+        self.program_counter += instruction.size();
+        let outcome = self.execute(instruction)?;
+        self.cycles += 1;
+        Ok(outcome)
+    }
+
+    /// Runs an already decoded instruction as if it had just been fetched, i.e. with the
+    /// program counter already past it.
+    pub fn execute(&mut self, instruction: Instruction) -> Result<StepOutcome, Chip8Error> {
+        use Instruction::*;
+
+        match instruction {
+            ScrollDown { n } => self.scroll_down(n as usize),
+            ScrollUp { n } => self.scroll_up(n as usize),
+            ClearScreen => self.clear_screen(),
+            Return => self.return_from_subroutine(),
+            ScrollRight => self.scroll_right(),
+            ScrollLeft => self.scroll_left(),
+            Exit => self.exit(),
+            Lores => self.lores(),
+            Hires => self.hires(),
+            JumpAddr { addr } => self.jmp_addr(addr),
+            CallAddr { addr } => self.call_addr(addr),
+            SkipEqByte { x, kk } => self.skip_register_eq(x as usize, kk),
+            SkipNeByte { x, kk } => self.skip_register_ne(x as usize, kk),
+            SkipEqXY { x, y } => self.skip_registers_eq(x as usize, y as usize),
+            StoreRange { x, y } => self.store_register_range(x as usize, y as usize),
+            LoadRange { x, y } => self.load_register_range(x as usize, y as usize),
+            SetByte { x, kk } => self.set_register(x as usize, kk),
+            AddByte { x, kk } => self.add_register(x as usize, kk),
+            LoadXY { x, y } => self.load_xy(x as usize, y as usize),
+            OrXY { x, y } => self.or_xy(x as usize, y as usize),
+            AndXY { x, y } => self.and_xy(x as usize, y as usize),
+            XorXY { x, y } => self.xor_xy(x as usize, y as usize),
+            AddXY { x, y } => self.add_xy(x as usize, y as usize),
+            SubXY { x, y } => self.sub_xy(x as usize, y as usize),
+            ShrXY { x, y } => self.shr_xy(x as usize, y as usize),
+            SubnXY { x, y } => self.subn_xy(x as usize, y as usize),
+            ShlXY { x, y } => self.shl_xy(x as usize, y as usize),
+            SkipNeXY { x, y } => self.skip_registers_ne(x as usize, y as usize),
+            SetI { addr } => self.set_i_register(addr),
+            JumpV0 { addr } => self.jmp_v0(addr),
+            Random { x, kk } => self.rand_byte(x as usize, kk),
+            Draw { x, y, n } => self.draw_sprite(x as usize, y as usize, n as usize),
+            SkipKey { x } => self.skip_vx_key(x as usize),
+            SkipNotKey { x } => self.nskip_vx_key(x as usize),
+            SetILong { addr } => self.set_i_register_long(addr),
+            SelectPlanes { n } => self.select_planes(n as usize),
+            LoadAudio => self.load_audio_pattern(),
+            LoadDelay { x } => self.load_delay(x as usize),
+            WaitKey { x } => self.wait_key(x as usize),
+            SetDelay { x } => self.set_delay(x as usize),
+            SetSound { x } => self.set_sound(x as usize),
+            AddI { x } => self.set_add_i_register(x as usize),
+            FontSprite { x } => self.set_sprite_location(x as usize),
+            BigFontSprite { x } => self.set_big_sprite_location(x as usize),
+            StoreBcd { x } => self.store_bcd(x as usize),
+            SetPitch { x } => self.set_pitch(x as usize),
+            StoreRegisters { x } => self.store_registers(x as usize),
+            LoadRegisters { x } => self.load_registers(x as usize),
+            StoreFlags { x } => self.store_rpl_flags(x as usize),
+            LoadFlags { x } => self.load_rpl_flags(x as usize),
+            Data(opcode) => Err(Chip8Error::UnknownOpcode { pc: self.current_instruction_address(), opcode }),
+        }?;
+
+        Ok(if self.halted {
+            StepOutcome::Halted
+        } else if self.waiting_for_key {
+            StepOutcome::WaitingForKey
+        } else {
+            StepOutcome::Executed
+        })
+    }
+
+    /// Decrements the delay and sound timers. Call this at 60 Hz, independent of how
+    /// many instructions are executed per second.
+    pub fn tick_timers(&mut self) {
         if self.delay_register > 0 { self.delay_register -= 1; }
         if self.sound_register > 0 { self.sound_register -= 1; }
     }
 
+    /// Runs one 60 Hz frame: `instructions_per_frame` instructions followed by a single
+    /// timer decrement. The effective clock rate is `instructions_per_frame * 60` Hz.
+    /// A frame ends early while `Fx0A` is waiting for a key or once the program halts.
+    pub fn run_frame(&mut self, instructions_per_frame: usize) -> Result<(), Chip8Error> {
+        for _ in 0..instructions_per_frame {
+            if self.tick()? != StepOutcome::Executed { break; }
+        }
+        self.tick_timers();
+        Ok(())
+    }
+
+}
+
+impl Default for Chip8Emulator {
+    fn default() -> Self { Self::blank(Quirks::default()) }
 }
@@ -0,0 +1,146 @@
+//! Input movies: the keypad state of every frame of a run, from power-on, so the run can be
+//! replayed exactly.
+//!
+//! Layout (little endian): the magic `C8MV`, a format version byte, the ROM hash (u64),
+//! instructions per frame (u32), the quirks and generator as in a save state, the RPL flags,
+//! a flag and u64 for the final state hash, the frame count (u32) and one u16 key mask per
+//! frame, bit n for key n.
+
+use crate::constants::*;
+use crate::error::Chip8Error;
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use crate::savestate::{fnv1a, StateReader, StateWriter};
+use crate::Chip8Emulator;
+
+const MAGIC: &[u8; 4] = b"C8MV";
+const MOVIE_VERSION: u8 = 2;
+
+/// A recorded run. Everything that decides how a program behaves besides its input is stored
+/// alongside the keys, so playback only needs the same ROM.
+///
+/// The movie runs the frames itself, through [`Self::record_frame`] and [`Self::play_frame`],
+/// so both always execute the same number of instructions. Anything else that changes the
+/// emulator in between, like single-stepping or memory edits, breaks the recording.
+#[derive(Clone, Debug)]
+pub struct Movie {
+    /// FNV-1a hash of the ROM the movie was recorded with.
+    pub rom_hash: u64,
+    /// Instructions run per frame, as given to [`Chip8Emulator::run_frame`].
+    pub instructions_per_frame: usize,
+    pub quirks: Quirks,
+    /// The generator as it was when the ROM was loaded. A [`Rng::Custom`] generator can be
+    /// recorded and played back within one session but not saved, see [`Self::from_bytes`].
+    pub rng: Rng,
+    pub rpl_flags: [u8; RPL_FLAG_COUNT],
+    /// The keypad state of each frame as a mask, bit n for key n.
+    pub frames: Vec<u16>,
+    /// [`Chip8Emulator::state_hash`] after the last frame, once recording has finished.
+    pub final_state_hash: Option<u64>,
+}
+
+impl Movie {
+    /// Loads `program` into `emulator` and starts a movie of it running
+    /// `instructions_per_frame` instructions a frame. Quirks, RPL flags and the generator are
+    /// taken from `emulator`.
+    pub fn record(emulator: &mut Chip8Emulator, program: &[u8], instructions_per_frame: usize) -> Result<Self, Chip8Error> {
+        emulator.load_rom(program)?;
+        Ok(Self {
+            rom_hash: fnv1a(program),
+            instructions_per_frame,
+            quirks: emulator.quirks,
+            rng: emulator.rng,
+            rpl_flags: emulator.rpl_flags,
+            frames: Vec::new(),
+            final_state_hash: None,
+        })
+    }
+
+    /// Appends the keys currently held in `emulator` as the next frame, then runs the frame.
+    pub fn record_frame(&mut self, emulator: &mut Chip8Emulator) -> Result<(), Chip8Error> {
+        self.frames.push(emulator.key_mask());
+        emulator.run_frame(self.instructions_per_frame)
+    }
+
+    /// Ends recording, remembering the state `emulator` ended up in.
+    pub fn finish(&mut self, emulator: &Chip8Emulator) {
+        self.final_state_hash = Some(emulator.state_hash());
+    }
+
+    /// Puts `emulator` back in the state recording started from, with `program` loaded.
+    pub fn start_playback(&self, emulator: &mut Chip8Emulator, program: &[u8]) -> Result<(), Chip8Error> {
+        if fnv1a(program) != self.rom_hash {
+            return Err(Chip8Error::MovieRomMismatch);
+        }
+        emulator.set_quirks(self.quirks);
+        emulator.set_rng(self.rng);
+        emulator.set_rpl_flags(self.rpl_flags);
+        emulator.load_rom(program)
+    }
+
+    /// Sets the keys of `frame` in `emulator` and runs the frame. Returns `false` without
+    /// running anything once the movie has no more frames.
+    pub fn play_frame(&self, emulator: &mut Chip8Emulator, frame: usize) -> Result<bool, Chip8Error> {
+        let Some(keys) = self.frames.get(frame) else { return Ok(false) };
+        emulator.set_key_mask(*keys);
+        emulator.run_frame(self.instructions_per_frame)?;
+        Ok(true)
+    }
+
+    /// Checks that playback ended in the state recording did. Movies that were never finished
+    /// have nothing to check against and always pass.
+    pub fn verify(&self, emulator: &Chip8Emulator) -> Result<(), Chip8Error> {
+        let actual = emulator.state_hash();
+        match self.final_state_hash {
+            Some(expected) if expected != actual => Err(Chip8Error::MovieDesync { expected, actual }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = StateWriter(Vec::with_capacity(64 + self.frames.len() * 2));
+
+        out.bytes(MAGIC);
+        out.u8(MOVIE_VERSION);
+        out.u64(self.rom_hash);
+        out.u32(self.instructions_per_frame as u32);
+        out.quirks(self.quirks);
+        out.rng(self.rng);
+        out.bytes(&self.rpl_flags);
+        out.bool(self.final_state_hash.is_some());
+        out.u64(self.final_state_hash.unwrap_or(0));
+        out.u32(self.frames.len() as u32);
+        for keys in &self.frames {
+            out.u16(*keys);
+        }
+
+        out.0
+    }
+
+    /// Reads a movie written by [`Self::to_bytes`]. Movies recorded with a [`Rng::Custom`]
+    /// generator are rejected, since the generator itself can't be stored.
+    pub fn from_bytes(movie: &[u8]) -> Result<Self, Chip8Error> {
+        Self::read(&mut StateReader(movie)).map_err(|_| Chip8Error::InvalidMovie)
+    }
+
+    fn read(input: &mut StateReader) -> Result<Self, Chip8Error> {
+        if input.take(MAGIC.len())? != MAGIC || input.u8()? != MOVIE_VERSION {
+            return Err(Chip8Error::InvalidMovie);
+        }
+
+        let rom_hash = input.u64()?;
+        let instructions_per_frame = input.u32()? as usize;
+        let quirks = input.quirks()?;
+        let rng = input.rng(Rng::default())?;
+        let mut rpl_flags = [0; RPL_FLAG_COUNT];
+        input.array(&mut rpl_flags)?;
+        let finished = input.bool()?;
+        let final_state_hash = Some(input.u64()?).filter(|_| finished);
+        let frames = (0..input.u32()?).map(|_| input.u16()).collect::<Result<_, _>>()?;
+        if !input.0.is_empty() {
+            return Err(Chip8Error::InvalidMovie);
+        }
+
+        Ok(Self { rom_hash, instructions_per_frame, quirks, rng, rpl_flags, frames, final_state_hash })
+    }
+}
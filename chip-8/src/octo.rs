@@ -0,0 +1,894 @@
+//! A compiler for Octo, the structured CHIP-8 assembly language.
+//!
+//! Supported: labels (`: name`), `:const`, `:alias`, `:calc`, `:macro`, `:byte`, `:org`,
+//! `:call`, `:unpack`, every register and `i` statement, `if ... then`,
+//! `if ... begin ... else ... end`, `loop ... while ... again`, bare numbers as sprite and other
+//! data, and bare labels as subroutine calls. `:proto`, `:breakpoint` and `:monitor` are
+//! accepted and ignored. A program with code or labels before `main` starts with a `jump main`
+//! at `0x200`.
+//!
+//! Tokens are separated by whitespace, including the braces and operators of `:calc`, and `#`
+//! starts a comment. As in Octo, `:calc` works on fractional numbers and its operators have
+//! no precedence: they group from the right, so `{ 2 * 3 + 1 }` is 8, and parentheses group
+//! as usual. Values are truncated toward zero where the program uses them.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::assembler::{Assembly, AssemblyError};
+use crate::constants::{MEMORY_SIZE, PROGRAM_START, XO_MEMORY_SIZE};
+use crate::instruction::Instruction;
+use crate::quirks::Quirks;
+use crate::symbols::SymbolMap;
+
+/// Macro expansions allowed per program, to stop runaway recursive macros.
+const MAX_MACRO_EXPANSIONS: usize = 100_000;
+
+/// The instruction set Octo source is compiled for. Each target accepts everything the ones
+/// before it do.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum OctoTarget {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl OctoTarget {
+    /// The quirks programs for this target expect to run with.
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Self::Chip8 => Quirks::COSMAC_VIP,
+            Self::SuperChip => Quirks::SUPER_CHIP,
+            Self::XoChip => Quirks::XO_CHIP,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Chip8 => "CHIP-8",
+            Self::SuperChip => "SCHIP",
+            Self::XoChip => "XO-CHIP",
+        }
+    }
+
+    fn memory_size(self) -> usize {
+        match self {
+            Self::XoChip => XO_MEMORY_SIZE,
+            _ => MEMORY_SIZE,
+        }
+    }
+}
+
+/// Compiles Octo source into a ROM image for loading at `0x200`, stopping at the first error.
+pub fn compile_octo(source: &str, target: OctoTarget) -> Result<Assembly, AssemblyError> {
+    let mut compiler = Compiler::new(tokenize(source), target);
+    compiler.compile()?;
+    compiler.finish()
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+fn tokenize(source: &str) -> VecDeque<Token> {
+    source.lines().enumerate()
+        .flat_map(|(i, line)| {
+            let code = line.split('#').next().unwrap_or("");
+            code.split_whitespace().map(move |text| Token { text: text.to_string(), line: i + 1 })
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// How a label's address is written into an already emitted instruction.
+#[derive(Copy, Clone)]
+enum Patch {
+    /// The low 12 bits of the word, as in `1nnn`, `2nnn`, `Annn` and `Bnnn`.
+    Addr,
+    /// A full 16-bit word, the operand of `i := long`.
+    Long,
+    /// `v0 := hi / v1 := lo` from `:unpack`, with the given nibble above a 12-bit address.
+    Unpack(u8),
+    /// `v0 := hi / v1 := lo` from `:unpack long`.
+    UnpackLong,
+}
+
+struct Fixup {
+    at: usize,
+    label: String,
+    patch: Patch,
+    line: usize,
+}
+
+/// An open `begin` or `loop`, waiting for its `end` or `again`.
+enum Block {
+    Begin { jump: usize, line: usize, has_else: bool },
+    Loop { start: usize, breaks: Vec<usize>, line: usize },
+}
+
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+#[derive(Copy, Clone)]
+enum Operand {
+    Register(u8),
+    Byte(u8),
+}
+
+enum Condition {
+    Key { x: u8, pressed: bool },
+    Compare { x: u8, comparison: Comparison, rhs: Operand },
+}
+
+struct Compiler {
+    tokens: VecDeque<Token>,
+    target: OctoTarget,
+    line: usize,
+    rom: Vec<u8>,
+    here: usize,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    blocks: Vec<Block>,
+    expansions: usize,
+    /// `main` is defined further on, so the first code emitted must be a jump to it.
+    main_pending: bool,
+}
+
+impl Compiler {
+    fn new(tokens: VecDeque<Token>, target: OctoTarget) -> Self {
+        Self {
+            tokens,
+            target,
+            line: 1,
+            rom: Vec::new(),
+            here: PROGRAM_START,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+            expansions: 0,
+            main_pending: false,
+        }
+    }
+
+    fn compile(&mut self) -> Result<(), AssemblyError> {
+        self.main_pending = self.tokens.iter().zip(self.tokens.iter().skip(1))
+            .any(|(colon, name)| colon.text == ":" && name.text == "main");
+
+        while let Some(token) = self.tokens.pop_front() {
+            self.line = token.line;
+            self.statement(token.text)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Assembly, AssemblyError> {
+        match self.blocks.last() {
+            Some(Block::Begin { line, .. }) => return Err(error_at(*line, "`begin` is never closed by `end`")),
+            Some(Block::Loop { line, .. }) => return Err(error_at(*line, "`loop` is never closed by `again`")),
+            None => {}
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(&address) = self.labels.get(&fixup.label) else {
+                return Err(error_at(fixup.line, format!("undefined label `{}`", fixup.label)));
+            };
+            self.patch(fixup.at, fixup.patch, address as i64).map_err(|message| error_at(fixup.line, message))?;
+        }
+
+        let mut symbols = SymbolMap::new();
+        for (name, address) in self.labels {
+            symbols.insert(name, address);
+        }
+        Ok(Assembly { bytes: self.rom, symbols })
+    }
+
+    fn statement(&mut self, token: String) -> Result<(), AssemblyError> {
+        use Instruction::*;
+
+        if let Some(definition) = self.macros.get(&token).cloned() {
+            return self.expand(definition);
+        }
+
+        match token.as_str() {
+            ":" => {
+                let name = self.next()?;
+                self.define(&name)?;
+                if name == "main" && self.here == PROGRAM_START {
+                    self.main_pending = false;
+                } else {
+                    self.jump_to_main()?;
+                }
+                self.labels.insert(name, self.here as u16);
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.value()?;
+                self.define(&name)?;
+                self.constants.insert(name, value as f64);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.define(&name)?;
+                self.aliases.insert(name, register);
+            }
+            ":calc" => {
+                let name = self.next()?;
+                self.expect("{")?;
+                let value = self.calc()?;
+                self.define(&name)?;
+                self.constants.insert(name, value);
+            }
+            ":byte" => {
+                let value = if self.peek() == Some("{") {
+                    self.next()?;
+                    self.calc()? as i64
+                } else {
+                    self.value()?
+                };
+                let byte = self.ranged(value, -0x80, 0xFF, "byte")?;
+                self.emit_byte(byte as u8)?;
+            }
+            ":org" => {
+                let address = self.value()?;
+                self.here = self.ranged(address, PROGRAM_START as i64, self.target.memory_size() as i64, "program address")? as usize;
+            }
+            ":call" => self.addressed(|addr| CallAddr { addr })?,
+            ":unpack" => {
+                let patch = if self.peek() == Some("long") {
+                    self.next()?;
+                    self.require(OctoTarget::XoChip, ":unpack long")?;
+                    Patch::UnpackLong
+                } else {
+                    let nibble = self.value()?;
+                    Patch::Unpack(self.ranged(nibble, 0, 0xF, "nibble")? as u8)
+                };
+                let label = self.next()?;
+                let at = self.here;
+                self.emit(SetByte { x: 0, kk: 0 })?;
+                self.emit(SetByte { x: 1, kk: 0 })?;
+                self.reference(label, at, patch)?;
+            }
+            ":macro" => self.define_macro()?,
+            ":proto" | ":breakpoint" => { self.next()?; }
+            ":monitor" => { self.next()?; self.next()?; }
+            ";" | "return" => self.emit(Return)?,
+            "clear" => self.emit(ClearScreen)?,
+            "exit" => self.super_chip(Exit, "exit")?,
+            "hires" => self.super_chip(Hires, "hires")?,
+            "lores" => self.super_chip(Lores, "lores")?,
+            "scroll-down" => {
+                self.require(OctoTarget::SuperChip, "scroll-down")?;
+                let n = self.nibble()?;
+                self.emit(ScrollDown { n })?;
+            }
+            "scroll-up" => {
+                self.require(OctoTarget::XoChip, "scroll-up")?;
+                let n = self.nibble()?;
+                self.emit(ScrollUp { n })?;
+            }
+            "scroll-left" => self.super_chip(ScrollLeft, "scroll-left")?,
+            "scroll-right" => self.super_chip(ScrollRight, "scroll-right")?,
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(StoreBcd { x })?;
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                let instruction = if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.register()?;
+                    self.require(OctoTarget::XoChip, "register ranges")?;
+                    if token == "save" { StoreRange { x, y } } else { LoadRange { x, y } }
+                } else if token == "save" {
+                    StoreRegisters { x }
+                } else {
+                    LoadRegisters { x }
+                };
+                self.emit(instruction)?;
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.super_chip(StoreFlags { x }, "saveflags")?;
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.super_chip(LoadFlags { x }, "loadflags")?;
+            }
+            "sprite" => {
+                let (x, y, n) = (self.register()?, self.register()?, self.nibble()?);
+                if n == 0 {
+                    self.require(OctoTarget::SuperChip, "16x16 sprites")?;
+                }
+                self.emit(Draw { x, y, n })?;
+            }
+            "jump" => self.addressed(|addr| JumpAddr { addr })?,
+            "jump0" => self.addressed(|addr| JumpV0 { addr })?,
+            "plane" => {
+                self.require(OctoTarget::XoChip, "plane")?;
+                let n = self.value()?;
+                let n = self.ranged(n, 0, 3, "plane mask")? as u8;
+                self.emit(SelectPlanes { n })?;
+            }
+            "audio" => {
+                self.require(OctoTarget::XoChip, "audio")?;
+                self.emit(LoadAudio)?;
+            }
+            "pitch" => {
+                self.require(OctoTarget::XoChip, "pitch")?;
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(SetPitch { x })?;
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(if token == "delay" { SetDelay { x } } else { SetSound { x } })?;
+            }
+            "i" => self.i_statement()?,
+            "if" => {
+                let condition = self.condition()?;
+                match self.next()?.as_str() {
+                    "then" => self.skip_unless(&condition, false)?,
+                    "begin" => {
+                        let line = self.line;
+                        self.skip_unless(&condition, true)?;
+                        let jump = self.placeholder_jump()?;
+                        self.blocks.push(Block::Begin { jump, line, has_else: false });
+                    }
+                    other => return Err(self.error(format!("expected `then` or `begin`, found `{other}`"))),
+                }
+            }
+            "else" => {
+                let Some(Block::Begin { jump, line, has_else }) = self.blocks.pop() else {
+                    return Err(self.error("`else` without `begin`"));
+                };
+                if has_else {
+                    return Err(self.error("`begin` already has an `else`"));
+                }
+                let skip_else = self.placeholder_jump()?;
+                self.patch_here(jump)?;
+                self.blocks.push(Block::Begin { jump: skip_else, line, has_else: true });
+            }
+            "end" => {
+                let Some(Block::Begin { jump, .. }) = self.blocks.pop() else {
+                    return Err(self.error("`end` without `begin`"));
+                };
+                self.patch_here(jump)?;
+            }
+            "loop" => {
+                // `again` has to come back here, not to the `jump main` written before the loop
+                self.jump_to_main()?;
+                self.blocks.push(Block::Loop { start: self.here, breaks: Vec::new(), line: self.line });
+            }
+            "while" => {
+                let condition = self.condition()?;
+                self.skip_unless(&condition, true)?;
+                let jump = self.placeholder_jump()?;
+                let Some(Block::Loop { breaks, .. }) = self.blocks.iter_mut().rev().find(|b| matches!(b, Block::Loop { .. })) else {
+                    return Err(self.error("`while` outside of a loop"));
+                };
+                breaks.push(jump);
+            }
+            "again" => {
+                let Some(Block::Loop { start, breaks, .. }) = self.blocks.pop() else {
+                    return Err(self.error("`again` without `loop`"));
+                };
+                let addr = self.ranged(start as i64, 0, 0xFFF, "address")? as u16;
+                self.emit(JumpAddr { addr })?;
+                for jump in breaks {
+                    self.patch_here(jump)?;
+                }
+            }
+            _ => {
+                if let Some(x) = self.parse_register(&token) {
+                    return self.register_statement(x);
+                }
+                if let Some(value) = parse_number(&token) {
+                    let byte = self.ranged(value, -0x80, 0xFF, "byte")?;
+                    return self.emit_byte(byte as u8);
+                }
+                let at = self.here;
+                self.emit(CallAddr { addr: 0 })?;
+                self.reference(token, at, Patch::Addr)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<(), AssemblyError> {
+        use Instruction::*;
+
+        let operator = self.next()?;
+        let instruction = match operator.as_str() {
+            ":=" => match self.peek() {
+                Some("random") => {
+                    self.next()?;
+                    Random { x, kk: self.byte()? }
+                }
+                Some("key") => { self.next()?; WaitKey { x } }
+                Some("delay") => { self.next()?; LoadDelay { x } }
+                _ => match self.operand()? {
+                    Operand::Register(y) => LoadXY { x, y },
+                    Operand::Byte(kk) => SetByte { x, kk },
+                },
+            },
+            "+=" => match self.operand()? {
+                Operand::Register(y) => AddXY { x, y },
+                Operand::Byte(kk) => AddByte { x, kk },
+            },
+            "-=" => match self.operand()? {
+                Operand::Register(y) => SubXY { x, y },
+                Operand::Byte(kk) => AddByte { x, kk: kk.wrapping_neg() },
+            },
+            "=-" => SubnXY { x, y: self.register()? },
+            "|=" => OrXY { x, y: self.register()? },
+            "&=" => AndXY { x, y: self.register()? },
+            "^=" => XorXY { x, y: self.register()? },
+            ">>=" => ShrXY { x, y: self.register()? },
+            "<<=" => ShlXY { x, y: self.register()? },
+            other => return Err(self.error(format!("unknown register operator `{other}`"))),
+        };
+        self.emit(instruction)
+    }
+
+    fn i_statement(&mut self) -> Result<(), AssemblyError> {
+        use Instruction::*;
+
+        match self.next()?.as_str() {
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.next()?;
+                    let x = self.register()?;
+                    self.emit(FontSprite { x })
+                }
+                Some("bighex") => {
+                    self.next()?;
+                    let x = self.register()?;
+                    self.super_chip(BigFontSprite { x }, "bighex")
+                }
+                Some("long") => {
+                    self.next()?;
+                    self.require(OctoTarget::XoChip, "i := long")?;
+                    self.emit(SetILong { addr: 0 })?;
+                    let at = self.here - 2;
+                    let label = self.next()?;
+                    self.reference(label, at, Patch::Long)
+                }
+                _ => self.addressed(|addr| SetI { addr }),
+            },
+            "+=" => {
+                let x = self.register()?;
+                self.emit(AddI { x })
+            }
+            other => Err(self.error(format!("unknown `i` operator `{other}`"))),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, AssemblyError> {
+        let x = self.register()?;
+        let comparison = match self.next()?.as_str() {
+            "key" => return Ok(Condition::Key { x, pressed: true }),
+            "-key" => return Ok(Condition::Key { x, pressed: false }),
+            "==" => Comparison::Eq,
+            "!=" => Comparison::Ne,
+            "<" => Comparison::Lt,
+            ">" => Comparison::Gt,
+            "<=" => Comparison::Le,
+            ">=" => Comparison::Ge,
+            other => return Err(self.error(format!("unknown comparison `{other}`"))),
+        };
+        Ok(Condition::Compare { x, comparison, rhs: self.operand()? })
+    }
+
+    /// Emits code that skips the next instruction unless `condition` is `expected`: with
+    /// `false` the next instruction runs only when the condition holds, as `then` needs, with
+    /// `true` only when it does not, as the jump after `begin` and `while` needs.
+    fn skip_unless(&mut self, condition: &Condition, expected: bool) -> Result<(), AssemblyError> {
+        use Instruction::*;
+
+        let (x, comparison, rhs) = match *condition {
+            Condition::Key { x, pressed } => {
+                return self.emit(if pressed == expected { SkipKey { x } } else { SkipNotKey { x } });
+            }
+            Condition::Compare { x, ref comparison, ref rhs } => (x, comparison, rhs),
+        };
+
+        let skip_if_equal = matches!(comparison, Comparison::Eq) == expected;
+        let (vf_minus, holds_on_borrow) = match comparison {
+            Comparison::Eq | Comparison::Ne => {
+                return self.emit(match (*rhs, skip_if_equal) {
+                    (Operand::Register(y), true) => SkipEqXY { x, y },
+                    (Operand::Register(y), false) => SkipNeXY { x, y },
+                    (Operand::Byte(kk), true) => SkipEqByte { x, kk },
+                    (Operand::Byte(kk), false) => SkipNeByte { x, kk },
+                });
+            }
+            Comparison::Lt => (true, true),
+            Comparison::Ge => (true, false),
+            Comparison::Gt => (false, true),
+            Comparison::Le => (false, false),
+        };
+
+        // VF := Vx - rhs (or rhs - Vx); VF is then 0 exactly when the subtraction borrowed.
+        match (*rhs, vf_minus) {
+            (Operand::Register(y), true) => {
+                self.emit(LoadXY { x: 0xF, y: x })?;
+                self.emit(SubXY { x: 0xF, y })?;
+            }
+            (Operand::Register(y), false) => {
+                self.emit(LoadXY { x: 0xF, y })?;
+                self.emit(SubXY { x: 0xF, y: x })?;
+            }
+            (Operand::Byte(kk), true) => {
+                self.emit(SetByte { x: 0xF, kk })?;
+                self.emit(SubnXY { x: 0xF, y: x })?;
+            }
+            (Operand::Byte(kk), false) => {
+                self.emit(SetByte { x: 0xF, kk })?;
+                self.emit(SubXY { x: 0xF, y: x })?;
+            }
+        }
+        let kk = if holds_on_borrow { 0 } else { 1 };
+        self.emit(if expected { SkipEqByte { x: 0xF, kk } } else { SkipNeByte { x: 0xF, kk } })
+    }
+
+    fn define_macro(&mut self) -> Result<(), AssemblyError> {
+        let name = self.next()?;
+        let mut params = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token == "{" { break; }
+            params.push(token);
+        }
+
+        let line = self.line;
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let Some(token) = self.tokens.pop_front() else {
+                return Err(error_at(line, format!("macro `{name}` is never closed by `}}`")));
+            };
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 { break; }
+            body.push(token);
+        }
+
+        self.define(&name)?;
+        self.macros.insert(name, Macro { params, body });
+        Ok(())
+    }
+
+    fn expand(&mut self, definition: Macro) -> Result<(), AssemblyError> {
+        self.expansions += 1;
+        if self.expansions > MAX_MACRO_EXPANSIONS {
+            return Err(self.error("too many macro expansions; is a macro calling itself?"));
+        }
+
+        let mut args = HashMap::new();
+        for param in &definition.params {
+            args.insert(param.clone(), self.next()?);
+        }
+        for token in definition.body.into_iter().rev() {
+            let text = args.get(&token.text).cloned().unwrap_or(token.text);
+            self.tokens.push_front(Token { text, line: self.line });
+        }
+        Ok(())
+    }
+
+    /// Evaluates a `:calc` expression up to its closing `}`.
+    fn calc(&mut self) -> Result<f64, AssemblyError> {
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token == "}" { break; }
+            tokens.push(token);
+        }
+        // `HERE` before anything is emitted is after the `jump main`
+        if tokens.iter().any(|token| token == "HERE") {
+            self.jump_to_main()?;
+        }
+
+        let mut position = 0;
+        let value = self.calc_expression(&tokens, &mut position)?;
+        if position != tokens.len() {
+            return Err(self.error(format!("unexpected `{}` in expression", tokens[position])));
+        }
+        Ok(value)
+    }
+
+    /// A term, optionally followed by an operator and the rest of the expression, which is
+    /// evaluated first.
+    fn calc_expression(&self, tokens: &[String], position: &mut usize) -> Result<f64, AssemblyError> {
+        let lhs = self.calc_term(tokens, position)?;
+        let Some(operator) = tokens.get(*position) else { return Ok(lhs) };
+        if !["|", "^", "&", "<<", ">>", "+", "-", "*", "/", "%"].contains(&operator.as_str()) {
+            return Ok(lhs);
+        }
+        *position += 1;
+
+        let rhs = self.calc_expression(tokens, position)?;
+        let (a, b) = (lhs as i64, rhs as i64);
+        Ok(match operator.as_str() {
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "&" => (a & b) as f64,
+            "<<" => a.wrapping_shl(b as u32) as f64,
+            ">>" => a.wrapping_shr(b as u32) as f64,
+            "+" => lhs + rhs,
+            "-" => lhs - rhs,
+            "*" => lhs * rhs,
+            _ if rhs == 0.0 => return Err(self.error("division by zero")),
+            "/" => lhs / rhs,
+            _ => lhs % rhs,
+        })
+    }
+
+    fn calc_term(&self, tokens: &[String], position: &mut usize) -> Result<f64, AssemblyError> {
+        let Some(token) = tokens.get(*position) else {
+            return Err(self.error("expression ends early"));
+        };
+        *position += 1;
+
+        match token.as_str() {
+            "-" => Ok(-self.calc_term(tokens, position)?),
+            "~" => Ok(!(self.calc_term(tokens, position)? as i64) as f64),
+            "(" => {
+                let value = self.calc_expression(tokens, position)?;
+                if tokens.get(*position).map(String::as_str) != Some(")") {
+                    return Err(self.error("missing `)`"));
+                }
+                *position += 1;
+                Ok(value)
+            }
+            "HERE" => Ok(self.here as f64),
+            _ => match self.constants.get(token.as_str()) {
+                Some(value) => Ok(*value),
+                None => self.lookup(token).map(|value| value as f64).ok_or_else(|| self.error(format!("undefined name `{token}`"))),
+            },
+        }
+    }
+
+    /// Emits an instruction taking an address, which may be a label defined later.
+    fn addressed(&mut self, instruction: impl Fn(u16) -> Instruction) -> Result<(), AssemblyError> {
+        let label = self.next()?;
+        let at = self.here;
+        self.emit(instruction(0))?;
+        self.reference(label, at, Patch::Addr)
+    }
+
+    /// Writes the value of `label` into the code at `at`, now if it is known, otherwise once
+    /// the whole program has been read.
+    fn reference(&mut self, label: String, at: usize, patch: Patch) -> Result<(), AssemblyError> {
+        if let Some(value) = self.lookup(&label) {
+            return self.patch(at, patch, value).map_err(|message| self.error(message));
+        }
+        if !is_identifier(&label) {
+            return Err(self.error(format!("`{label}` is not a valid name")));
+        }
+        self.fixups.push(Fixup { at, label, patch, line: self.line });
+        Ok(())
+    }
+
+    fn patch(&mut self, at: usize, patch: Patch, value: i64) -> Result<(), String> {
+        let offset = at - PROGRAM_START;
+        let limit = if matches!(patch, Patch::Long | Patch::UnpackLong) { 0xFFFF } else { 0xFFF };
+        if !(0..=limit).contains(&value) {
+            return Err(format!("address 0x{value:X} is out of range"));
+        }
+        let [high, low] = (value as u16).to_be_bytes();
+
+        match patch {
+            Patch::Addr => {
+                self.rom[offset] = (self.rom[offset] & 0xF0) | high;
+                self.rom[offset + 1] = low;
+            }
+            Patch::Long => {
+                self.rom[offset] = high;
+                self.rom[offset + 1] = low;
+            }
+            Patch::Unpack(nibble) => {
+                self.rom[offset + 1] = nibble << 4 | high;
+                self.rom[offset + 3] = low;
+            }
+            Patch::UnpackLong => {
+                self.rom[offset + 1] = high;
+                self.rom[offset + 3] = low;
+            }
+        }
+        Ok(())
+    }
+
+    fn placeholder_jump(&mut self) -> Result<usize, AssemblyError> {
+        let at = self.here;
+        self.emit(Instruction::JumpAddr { addr: 0 })?;
+        Ok(at)
+    }
+
+    fn patch_here(&mut self, jump: usize) -> Result<(), AssemblyError> {
+        let here = self.here as i64;
+        self.patch(jump, Patch::Addr, here).map_err(|message| self.error(message))
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> Result<(), AssemblyError> {
+        let (opcode, operand) = instruction.encode();
+        for word in [Some(opcode), operand].into_iter().flatten() {
+            let [high, low] = word.to_be_bytes();
+            self.emit_byte(high)?;
+            self.emit_byte(low)?;
+        }
+        Ok(())
+    }
+
+    /// Emits the `jump main` owed by programs that put other code or labels before `main`. It
+    /// always goes at the start of the program, wherever `:org` has moved the output to.
+    fn jump_to_main(&mut self) -> Result<(), AssemblyError> {
+        if !self.main_pending { return Ok(()); }
+        self.main_pending = false;
+        let here = self.here;
+        self.here = PROGRAM_START;
+        self.emit(Instruction::JumpAddr { addr: 0 })?;
+        self.reference("main".to_string(), PROGRAM_START, Patch::Addr)?;
+        self.here = here.max(self.here);
+        Ok(())
+    }
+
+    fn emit_byte(&mut self, byte: u8) -> Result<(), AssemblyError> {
+        self.jump_to_main()?;
+        if self.here >= self.target.memory_size() {
+            return Err(self.error("program does not fit in memory"));
+        }
+        let offset = self.here - PROGRAM_START;
+        if offset >= self.rom.len() {
+            self.rom.resize(offset + 1, 0);
+        }
+        self.rom[offset] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn super_chip(&mut self, instruction: Instruction, what: &str) -> Result<(), AssemblyError> {
+        self.require(OctoTarget::SuperChip, what)?;
+        self.emit(instruction)
+    }
+
+    fn require(&self, target: OctoTarget, what: &str) -> Result<(), AssemblyError> {
+        if self.target < target {
+            return Err(self.error(format!("`{what}` needs {} but the target is {}", target.name(), self.target.name())));
+        }
+        Ok(())
+    }
+
+    fn define(&self, name: &str) -> Result<(), AssemblyError> {
+        if !is_identifier(name) || self.parse_register(name).is_some() {
+            return Err(self.error(format!("`{name}` is not a valid name")));
+        }
+        let taken = self.labels.contains_key(name) || self.constants.contains_key(name)
+            || self.aliases.contains_key(name) || self.macros.contains_key(name);
+        if taken {
+            return Err(self.error(format!("`{name}` is already defined")));
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Option<i64> {
+        parse_number(name)
+            .or_else(|| self.constants.get(name).map(|&value| value as i64))
+            .or_else(|| self.labels.get(name).map(|&address| address as i64))
+    }
+
+    fn parse_register(&self, token: &str) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(token) {
+            return Some(register);
+        }
+        let digit = token.strip_prefix(['v', 'V'])?;
+        if digit.len() != 1 { return None; }
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    fn register(&mut self) -> Result<u8, AssemblyError> {
+        let token = self.next()?;
+        self.parse_register(&token).ok_or_else(|| self.error(format!("expected a register, found `{token}`")))
+    }
+
+    fn operand(&mut self) -> Result<Operand, AssemblyError> {
+        if let Some(register) = self.peek().and_then(|token| self.parse_register(token)) {
+            self.next()?;
+            return Ok(Operand::Register(register));
+        }
+        Ok(Operand::Byte(self.byte()?))
+    }
+
+    fn byte(&mut self) -> Result<u8, AssemblyError> {
+        let value = self.value()?;
+        Ok(self.ranged(value, -0x80, 0xFF, "byte")? as u8)
+    }
+
+    fn nibble(&mut self) -> Result<u8, AssemblyError> {
+        let value = self.value()?;
+        Ok(self.ranged(value, 0, 0xF, "nibble")? as u8)
+    }
+
+    fn value(&mut self) -> Result<i64, AssemblyError> {
+        let token = self.next()?;
+        self.lookup(&token).ok_or_else(|| self.error(format!("undefined name `{token}`")))
+    }
+
+    fn ranged(&self, value: i64, min: i64, max: i64, what: &str) -> Result<i64, AssemblyError> {
+        if value < min || value > max {
+            let article = if what.starts_with(['a', 'e', 'i', 'o', 'u']) { "an" } else { "a" };
+            return Err(self.error(format!("{value} does not fit in {article} {what}")));
+        }
+        Ok(value)
+    }
+
+    fn next(&mut self) -> Result<String, AssemblyError> {
+        let token = self.tokens.pop_front().ok_or_else(|| self.error("unexpected end of program"))?;
+        self.line = token.line;
+        Ok(token.text)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), AssemblyError> {
+        let token = self.next()?;
+        if token != expected {
+            return Err(self.error(format!("expected `{expected}`, found `{token}`")));
+        }
+        Ok(())
+    }
+
+    fn error(&self, message: impl Into<String>) -> AssemblyError {
+        error_at(self.line, message)
+    }
+}
+
+fn error_at(line: usize, message: impl Into<String>) -> AssemblyError {
+    AssemblyError { line, message: message.into() }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn parse_number(token: &str) -> Option<i64> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, token),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
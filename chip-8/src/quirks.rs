@@ -0,0 +1,101 @@
+use crate::constants::{MEMORY_SIZE, XO_MEMORY_SIZE};
+
+/// How `Fx55` / `Fx65` leave the I register once they finish.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IndexIncrement {
+    /// I is left pointing past the last register touched (I += x + 1).
+    XPlusOne,
+    /// I is advanced by x, one short of the registers touched (I += x).
+    X,
+    /// I is not modified.
+    Unchanged,
+}
+
+/// Behaviors that differ between CHIP-8 implementations.
+///
+/// Each field names a point where interpreters disagree; the presets below
+/// match the platforms ROMs were most commonly written for.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quirks {
+    /// `8xy1` / `8xy2` / `8xy3` reset VF to 0.
+    pub vf_reset: bool,
+    /// `8xy6` / `8xyE` shift Vy into Vx instead of shifting Vx in place.
+    pub shift_uses_vy: bool,
+    /// What `Fx55` / `Fx65` do to I.
+    pub index_increment: IndexIncrement,
+    /// `Bnnn` jumps to nnn + Vx (x being the high nibble of nnn) instead of nnn + V0.
+    pub jump_uses_vx: bool,
+    /// `Fx0A` finishes when a key is released, as on the VIP, rather than as soon as one is
+    /// pressed.
+    pub wait_key_release: bool,
+    /// `Dxyn` wraps pixels that fall off the left or right edge to the opposite side instead
+    /// of clipping them.
+    pub wrap_sprites_x: bool,
+    /// `Dxyn` wraps pixels that fall off the bottom edge to the top instead of clipping them.
+    pub wrap_sprites_y: bool,
+    /// `00FE` / `00FF` clear the screen when they switch resolution, as in Octo. SUPER-CHIP 1.1
+    /// leaves the picture as it was.
+    pub clear_on_resolution_change: bool,
+    /// Size of the address space in bytes: 4 KiB everywhere except XO-CHIP's 64 KiB.
+    pub memory_size: usize,
+}
+
+impl Quirks {
+    /// The original interpreter on the RCA COSMAC VIP.
+    pub const COSMAC_VIP: Self = Self {
+        vf_reset: true,
+        shift_uses_vy: true,
+        index_increment: IndexIncrement::XPlusOne,
+        jump_uses_vx: false,
+        wait_key_release: true,
+        wrap_sprites_x: false,
+        wrap_sprites_y: false,
+        clear_on_resolution_change: false,
+        memory_size: MEMORY_SIZE,
+    };
+
+    /// CHIP-48 on the HP-48 calculators.
+    pub const CHIP_48: Self = Self {
+        vf_reset: false,
+        shift_uses_vy: false,
+        index_increment: IndexIncrement::X,
+        jump_uses_vx: true,
+        wait_key_release: false,
+        wrap_sprites_x: false,
+        wrap_sprites_y: false,
+        clear_on_resolution_change: false,
+        memory_size: MEMORY_SIZE,
+    };
+
+    /// SUPER-CHIP 1.1.
+    pub const SUPER_CHIP: Self = Self {
+        vf_reset: false,
+        shift_uses_vy: false,
+        index_increment: IndexIncrement::Unchanged,
+        jump_uses_vx: true,
+        wait_key_release: false,
+        wrap_sprites_x: false,
+        wrap_sprites_y: false,
+        clear_on_resolution_change: false,
+        memory_size: MEMORY_SIZE,
+    };
+
+    /// XO-CHIP as implemented by Octo.
+    pub const XO_CHIP: Self = Self {
+        vf_reset: false,
+        shift_uses_vy: true,
+        index_increment: IndexIncrement::XPlusOne,
+        jump_uses_vx: false,
+        wait_key_release: true,
+        wrap_sprites_x: true,
+        wrap_sprites_y: true,
+        clear_on_resolution_change: true,
+        memory_size: XO_MEMORY_SIZE,
+    };
+}
+
+impl Default for Quirks {
+    fn default() -> Self { Self::COSMAC_VIP }
+}
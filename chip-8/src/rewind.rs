@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+
+use crate::error::Chip8Error;
+use crate::Chip8Emulator;
+
+/// A ring buffer of recent emulator snapshots for stepping backwards in time.
+///
+/// Only the newest snapshot is kept whole; each older one is stored as the XOR of itself with
+/// its successor, run-length encoded, which is tiny since most memory is unchanged between frames.
+#[derive(Clone, Debug)]
+pub struct RewindBuffer {
+    depth: usize,
+    interval: usize,
+    frames_since_snapshot: usize,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl RewindBuffer {
+    /// Keeps up to `depth` snapshots taken every `interval` frames, covering
+    /// `depth * interval` frames of history.
+    pub fn new(depth: usize, interval: usize) -> Self {
+        Self {
+            depth,
+            interval: interval.max(1),
+            frames_since_snapshot: 0,
+            latest: None,
+            deltas: VecDeque::with_capacity(depth),
+        }
+    }
+
+    /// Call once per emulated frame; takes a snapshot every `interval` calls.
+    pub fn record(&mut self, emulator: &Chip8Emulator) {
+        self.frames_since_snapshot += 1;
+        if self.latest.is_some() && self.frames_since_snapshot < self.interval { return; }
+        self.frames_since_snapshot = 0;
+
+        let snapshot = emulator.save_state();
+        if let Some(latest) = self.latest.take() {
+            if latest.len() != snapshot.len() {
+                self.deltas.clear();
+            } else {
+                self.deltas.push_back(encode_delta(&snapshot, &latest));
+                if self.deltas.len() > self.depth {
+                    self.deltas.pop_front();
+                }
+            }
+        }
+        self.latest = Some(snapshot);
+    }
+
+    /// Restores the emulator to roughly `frames` frames ago, rounded up to the snapshot interval
+    /// and limited by the recorded history. Returns how many frames were actually rewound.
+    ///
+    /// Fails when the snapshot can't be restored into `emulator`, e.g. one recorded with a
+    /// [`crate::Rng::Custom`] generator the emulator no longer has; the history is kept as it
+    /// was.
+    pub fn rewind(&mut self, emulator: &mut Chip8Emulator, frames: usize) -> Result<usize, Chip8Error> {
+        let Some(latest) = self.latest.as_ref() else { return Ok(0) };
+
+        let steps = frames.div_ceil(self.interval).min(self.deltas.len());
+        if steps == 0 { return Ok(0) }
+        let mut snapshot = latest.clone();
+        for delta in self.deltas.iter().rev().take(steps) {
+            apply_delta(&mut snapshot, delta);
+        }
+
+        emulator.load_state(&snapshot)?;
+        self.deltas.truncate(self.deltas.len() - steps);
+        self.latest = Some(snapshot);
+        self.frames_since_snapshot = 0;
+        Ok(steps * self.interval)
+    }
+
+    /// Number of frames of history currently available to [`Self::rewind`].
+    pub fn available_frames(&self) -> usize {
+        self.deltas.len() * self.interval
+    }
+
+    pub fn clear(&mut self) {
+        self.frames_since_snapshot = 0;
+        self.latest = None;
+        self.deltas.clear();
+    }
+}
+
+/// Encodes `old ^ new` as alternating runs: a varint count of unchanged bytes, a varint count
+/// of changed bytes, then the changed bytes themselves.
+fn encode_delta(new: &[u8], old: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < new.len() {
+        let unchanged = new[i..].iter().zip(&old[i..]).take_while(|(a, b)| a == b).count();
+        i += unchanged;
+        let changed = new[i..].iter().zip(&old[i..]).take_while(|(a, b)| a != b).count();
+
+        write_varint(&mut out, unchanged);
+        write_varint(&mut out, changed);
+        out.extend(new[i..i + changed].iter().zip(&old[i..i + changed]).map(|(a, b)| a ^ b));
+        i += changed;
+    }
+    out
+}
+
+fn apply_delta(target: &mut [u8], delta: &[u8]) {
+    let mut i = 0;
+    let mut input = delta;
+    while !input.is_empty() {
+        i += read_varint(&mut input);
+        let changed = read_varint(&mut input);
+        for (byte, diff) in target[i..i + changed].iter_mut().zip(&input[..changed]) {
+            *byte ^= diff;
+        }
+        input = &input[changed..];
+        i += changed;
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = input[0];
+        *input = &input[1..];
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 { return value; }
+        shift += 7;
+    }
+}
@@ -0,0 +1,108 @@
+/// A generator for [`Rng::custom`]. Its whole state has to fit in a `u64` so that save states
+/// and movies can capture it, which also keeps the emulator `Copy`.
+pub trait RandomSource {
+    /// Produces the next byte, advancing `state`.
+    fn next_byte(state: &mut u64) -> u8;
+}
+
+/// SplitMix64: fast, accepts any seed including 0, and good enough for games.
+pub struct SplitMix;
+
+impl RandomSource for SplitMix {
+    fn next_byte(state: &mut u64) -> u8 {
+        split_mix(state)
+    }
+}
+
+const fn split_mix(state: &mut u64) -> u8 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    ((z ^ (z >> 31)) >> 56) as u8
+}
+
+/// What [`Rng::CosmacVip`] reads in place of the VIP's interpreter page at 0x100-0x1FF: fixed
+/// bytes out of [`SplitMix`], so VIP-mode sequences don't depend on the program in memory.
+const VIP_PAGE: [u8; 256] = {
+    let mut page = [0; 256];
+    let mut state = 0x0100_u64;
+    let mut i = 0;
+    while i < page.len() {
+        page[i] = split_mix(&mut state);
+        i += 1;
+    }
+    page
+};
+
+/// Seed of [`Rng::default`], so runs are reproducible unless a frontend seeds on purpose.
+pub const DEFAULT_SEED: u64 = 0xC8;
+
+/// Where `Cxkk` gets its random bytes from.
+#[derive(Copy, Clone, Debug)]
+pub enum Rng {
+    /// [`SplitMix`] from a seed.
+    Seeded { state: u64 },
+    /// The VIP interpreter's routine: it increments R9, adds the byte at `0x100 + R9.0` to
+    /// R9.1 and returns R9.1. On the VIP that page holds interpreter code, which isn't bundled;
+    /// a fixed page of generated bytes stands in for it, so the numbers follow the same
+    /// pattern without being identical.
+    CosmacVip { r9: u16 },
+    /// A [`RandomSource`] chosen by the embedder. `next` is a plain function over the `u64`
+    /// state, not a boxed trait object, so the emulator stays `Copy`: a generator can't capture
+    /// anything, hold more than 64 bits of state, or reach the emulator's memory. Save states
+    /// keep only `state` and restore onto an emulator already using the same `next`, and
+    /// movies recorded with one can't be read back.
+    Custom { state: u64, next: fn(&mut u64) -> u8 },
+}
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Self {
+        Self::Seeded { state: seed }
+    }
+
+    /// The VIP routine, starting from `r9`.
+    pub fn cosmac_vip(r9: u16) -> Self {
+        Self::CosmacVip { r9 }
+    }
+
+    /// `S` starting from `seed`, with the limitations described on [`Self::Custom`].
+    pub fn custom<S: RandomSource>(seed: u64) -> Self {
+        Self::Custom { state: seed, next: S::next_byte }
+    }
+
+    /// The generator state, enough to resume the same sequence with the same kind of `Rng`.
+    pub fn state(&self) -> u64 {
+        match *self {
+            Self::Seeded { state } | Self::Custom { state, .. } => state,
+            Self::CosmacVip { r9 } => r9 as u64,
+        }
+    }
+
+    /// This generator with its state replaced, as captured by [`Self::state`].
+    pub fn with_state(self, state: u64) -> Self {
+        match self {
+            Self::Seeded { .. } => Self::Seeded { state },
+            Self::CosmacVip { .. } => Self::CosmacVip { r9: state as u16 },
+            Self::Custom { next, .. } => Self::Custom { state, next },
+        }
+    }
+
+    pub(crate) fn next_byte(&mut self) -> u8 {
+        match self {
+            Self::Seeded { state } => SplitMix::next_byte(state),
+            Self::CosmacVip { r9 } => {
+                *r9 = r9.wrapping_add(1);
+                let [high, low] = r9.to_be_bytes();
+                let high = high.wrapping_add(VIP_PAGE[low as usize]);
+                *r9 = u16::from_be_bytes([high, low]);
+                high
+            }
+            Self::Custom { state, next } => next(state),
+        }
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self { Self::seeded(DEFAULT_SEED) }
+}
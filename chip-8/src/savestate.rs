@@ -0,0 +1,277 @@
+//! Versioned binary snapshots of the complete machine state.
+//!
+//! Layout (little endian): the magic `C8ST`, a format version byte, then every field of
+//! [`Chip8Emulator`] in declaration order with fixed sizes, so every snapshot of a given
+//! version has the same length. The framebuffer is a hi-res flag followed by every row of
+//! each plane as a u128.
+
+use crate::constants::*;
+use crate::error::Chip8Error;
+use crate::framebuffer::Framebuffer;
+use crate::quirks::{IndexIncrement, Quirks};
+use crate::rng::Rng;
+use crate::Chip8Emulator;
+
+const MAGIC: &[u8; 4] = b"C8ST";
+/// Stands in for `None` where an optional key is stored.
+const NO_KEY: u8 = 0xFF;
+pub const SAVE_STATE_VERSION: u8 = 7;
+
+impl Chip8Emulator {
+    /// Serializes the complete machine state, quirks and generator state included.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = StateWriter(Vec::with_capacity(XO_MEMORY_SIZE + 0x2100));
+
+        out.bytes(MAGIC);
+        out.u8(SAVE_STATE_VERSION);
+
+        out.quirks(self.quirks);
+
+        out.bytes(&self.memory);
+        out.bool(self.display_width() == HIRES_DISPLAY_WIDTH);
+        for row in self.framebuffer.planes.as_flattened() {
+            out.u128(*row);
+        }
+        out.u8(self.selected_planes);
+
+        out.bytes(&self.v_registers);
+        out.u16(self.i_register);
+        out.u8(self.delay_register);
+        out.u8(self.sound_register);
+        out.bytes(&self.audio_pattern);
+        out.u8(self.pitch);
+
+        out.u32(self.program_counter as u32);
+        out.u8(self.stack_pointer as u8);
+        for entry in self.stack {
+            out.u16(entry);
+        }
+        out.bytes(&self.rpl_flags);
+
+        out.u16(self.key_mask());
+        out.bool(self.waiting_for_key);
+        out.u8(self.key_event.map_or(NO_KEY, |key| key));
+        out.bool(self.halted);
+        out.u64(self.cycles);
+        out.rng(self.rng);
+
+        out.0
+    }
+
+    /// Restores a snapshot produced by [`Self::save_state`]. The emulator is left untouched if
+    /// the snapshot is malformed or from another format version.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), Chip8Error> {
+        let mut input = StateReader(state);
+
+        if input.take(MAGIC.len())? != MAGIC {
+            return Err(Chip8Error::InvalidSaveState);
+        }
+        let version = input.u8()?;
+        if version != SAVE_STATE_VERSION {
+            return Err(Chip8Error::UnsupportedSaveStateVersion(version));
+        }
+
+        let mut emulator = Chip8Emulator::blank(input.quirks()?);
+
+        input.array(&mut emulator.memory)?;
+        emulator.framebuffer = match input.bool()? {
+            true => Framebuffer::new(HIRES_DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT),
+            false => Framebuffer::new(DISPLAY_WIDTH, DISPLAY_HEIGHT),
+        };
+        for row in emulator.framebuffer.planes.as_flattened_mut() {
+            *row = input.u128()?;
+        }
+        emulator.selected_planes = input.u8()?;
+
+        input.array(&mut emulator.v_registers)?;
+        emulator.i_register = input.u16()?;
+        emulator.delay_register = input.u8()?;
+        emulator.sound_register = input.u8()?;
+        input.array(&mut emulator.audio_pattern)?;
+        emulator.pitch = input.u8()?;
+
+        emulator.program_counter = input.u32()? as usize;
+        emulator.stack_pointer = input.u8()? as usize;
+        for entry in emulator.stack.iter_mut() {
+            *entry = input.u16()?;
+        }
+        input.array(&mut emulator.rpl_flags)?;
+
+        let keys = input.u16()?;
+        for (i, pressed) in emulator.key_flags.iter_mut().enumerate() {
+            *pressed = keys & (1 << i) != 0;
+        }
+        emulator.waiting_for_key = input.bool()?;
+        emulator.key_event = match input.u8()? {
+            NO_KEY => None,
+            key if (key as usize) < KEY_COUNT => Some(key),
+            _ => return Err(Chip8Error::InvalidSaveState),
+        };
+        emulator.halted = input.bool()?;
+        emulator.cycles = input.u64()?;
+        emulator.rng = input.rng(self.rng)?;
+
+        let valid = input.0.is_empty()
+            && emulator.quirks.memory_size <= XO_MEMORY_SIZE
+            && emulator.program_counter < XO_MEMORY_SIZE
+            && emulator.stack_pointer <= STACK_SIZE
+            && emulator.selected_planes <= 0b11;
+        if !valid {
+            return Err(Chip8Error::InvalidSaveState);
+        }
+
+        *self = emulator;
+        Ok(())
+    }
+
+    /// A 64-bit FNV-1a hash of [`Self::save_state`], for checking that two runs ended up in
+    /// exactly the same state.
+    pub fn state_hash(&self) -> u64 {
+        fnv1a(&self.save_state())
+    }
+}
+
+/// 64-bit FNV-1a: not cryptographic, just stable across platforms and builds.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01B3))
+}
+
+/// Little-endian field writer shared by save states and movies.
+pub(crate) struct StateWriter(pub(crate) Vec<u8>);
+
+impl StateWriter {
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) { self.0.extend_from_slice(bytes); }
+    pub(crate) fn u8(&mut self, value: u8) { self.0.push(value); }
+    pub(crate) fn bool(&mut self, value: bool) { self.0.push(value as u8); }
+    pub(crate) fn u16(&mut self, value: u16) { self.bytes(&value.to_le_bytes()); }
+    pub(crate) fn u32(&mut self, value: u32) { self.bytes(&value.to_le_bytes()); }
+    pub(crate) fn u64(&mut self, value: u64) { self.bytes(&value.to_le_bytes()); }
+    pub(crate) fn u128(&mut self, value: u128) { self.bytes(&value.to_le_bytes()); }
+
+    pub(crate) fn quirks(&mut self, quirks: Quirks) {
+        self.bool(quirks.vf_reset);
+        self.bool(quirks.shift_uses_vy);
+        self.u8(match quirks.index_increment {
+            IndexIncrement::XPlusOne => 0,
+            IndexIncrement::X => 1,
+            IndexIncrement::Unchanged => 2,
+        });
+        self.bool(quirks.jump_uses_vx);
+        self.bool(quirks.wait_key_release);
+        self.bool(quirks.wrap_sprites_x);
+        self.bool(quirks.wrap_sprites_y);
+        self.bool(quirks.clear_on_resolution_change);
+        self.u32(quirks.memory_size as u32);
+    }
+
+    pub(crate) fn rng(&mut self, rng: Rng) {
+        self.u8(match rng {
+            Rng::Seeded { .. } => 0,
+            Rng::CosmacVip { .. } => 1,
+            Rng::Custom { .. } => 2,
+        });
+        self.u64(rng.state());
+    }
+}
+
+/// Reads what [`StateWriter`] wrote; running out of input is [`Chip8Error::InvalidSaveState`].
+pub(crate) struct StateReader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> StateReader<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        if self.0.len() < len {
+            return Err(Chip8Error::InvalidSaveState);
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn array(&mut self, target: &mut [u8]) -> Result<(), Chip8Error> {
+        target.copy_from_slice(self.take(target.len())?);
+        Ok(())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Chip8Error> { Ok(self.take(1)?[0]) }
+    pub(crate) fn bool(&mut self) -> Result<bool, Chip8Error> { Ok(self.u8()? != 0) }
+    pub(crate) fn u16(&mut self) -> Result<u16, Chip8Error> { Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap())) }
+    pub(crate) fn u32(&mut self) -> Result<u32, Chip8Error> { Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap())) }
+    pub(crate) fn u64(&mut self) -> Result<u64, Chip8Error> { Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap())) }
+    pub(crate) fn u128(&mut self) -> Result<u128, Chip8Error> { Ok(u128::from_le_bytes(self.take(16)?.try_into().unwrap())) }
+
+    pub(crate) fn quirks(&mut self) -> Result<Quirks, Chip8Error> {
+        Ok(Quirks {
+            vf_reset: self.bool()?,
+            shift_uses_vy: self.bool()?,
+            index_increment: match self.u8()? {
+                0 => IndexIncrement::XPlusOne,
+                1 => IndexIncrement::X,
+                2 => IndexIncrement::Unchanged,
+                _ => return Err(Chip8Error::InvalidSaveState),
+            },
+            jump_uses_vx: self.bool()?,
+            wait_key_release: self.bool()?,
+            wrap_sprites_x: self.bool()?,
+            wrap_sprites_y: self.bool()?,
+            clear_on_resolution_change: self.bool()?,
+            memory_size: self.u32()? as usize,
+        })
+    }
+
+    /// A custom generator is code, not data, so it can only be restored on top of `current`
+    /// when that is already one.
+    pub(crate) fn rng(&mut self, current: Rng) -> Result<Rng, Chip8Error> {
+        let rng = match self.u8()? {
+            0 => Rng::seeded(0),
+            1 => Rng::cosmac_vip(0),
+            2 if matches!(current, Rng::Custom { .. }) => current,
+            _ => return Err(Chip8Error::InvalidSaveState),
+        };
+        Ok(rng.with_state(self.u64()?))
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use serde::de::{Error, Visitor};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::Chip8Emulator;
+
+    /// Serializes as the binary save state, so every serde format shares the same versioning.
+    impl Serialize for Chip8Emulator {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(&self.save_state())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Chip8Emulator {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct StateVisitor;
+
+            impl<'de> Visitor<'de> for StateVisitor {
+                type Value = Chip8Emulator;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    write!(f, "a CHIP-8 save state")
+                }
+
+                fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                    let mut emulator = Chip8Emulator::default();
+                    emulator.load_state(v).map_err(E::custom)?;
+                    Ok(emulator)
+                }
+
+                fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                    let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                    while let Some(byte) = seq.next_element()? {
+                        bytes.push(byte);
+                    }
+                    self.visit_bytes(&bytes)
+                }
+            }
+
+            deserializer.deserialize_bytes(StateVisitor)
+        }
+    }
+}
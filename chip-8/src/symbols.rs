@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::assembler::AssemblyError;
+
+/// Label addresses of an assembled program, so tools can show names instead of raw addresses.
+///
+/// The text form written by `chip8-asm` has one `0x0200 name` line per label, sorted by
+/// address. Blank lines and `;` comments are ignored when reading it back.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SymbolMap {
+    labels: BTreeMap<String, u16>,
+}
+
+impl SymbolMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, address: u16) {
+        self.labels.insert(name.into(), address);
+    }
+
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.labels.get(name).copied()
+    }
+
+    /// The alphabetically first label at `address`, if any.
+    pub fn label_at(&self, address: u16) -> Option<&str> {
+        self.labels.iter().find(|(_, a)| **a == address).map(|(name, _)| name.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u16)> {
+        self.labels.iter().map(|(name, address)| (name.as_str(), *address))
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
+
+impl Display for SymbolMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut entries = self.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(name, address)| (*address, *name));
+        for (name, address) in entries {
+            writeln!(f, "0x{address:04X} {name}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SymbolMap {
+    type Err = AssemblyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut symbols = Self::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() { continue; }
+
+            let error = |message: &str| AssemblyError { line: i + 1, message: message.to_string() };
+            let (address, name) = line.split_once(char::is_whitespace).ok_or_else(|| error("expected an address and a name"))?;
+            let address = u16::from_str_radix(address.trim_start_matches("0x"), 16)
+                .map_err(|_| error("invalid address"))?;
+            symbols.insert(name.trim(), address);
+        }
+        Ok(symbols)
+    }
+}
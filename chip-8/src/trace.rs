@@ -0,0 +1,278 @@
+use std::fmt::Display;
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+use crate::debugger::{Register, Registers};
+use crate::disassembler::disassemble;
+use crate::error::{Chip8Error, StepOutcome};
+use crate::Chip8Emulator;
+
+const MAGIC: &[u8; 4] = b"C8TR";
+const TRACE_VERSION: u8 = 3;
+
+/// Change-mask bit of a record for an instruction that faulted.
+const FAULT_BIT: u32 = 1 << 31;
+/// Change-mask bit of a record carrying the second word of `F000 nnnn`.
+const OPERAND_BIT: u32 = 1 << 30;
+
+/// Registers a trace record can carry a change for, in change-mask bit order.
+const TRACED_REGISTERS: [Register; 20] = [
+    Register::V(0x0), Register::V(0x1), Register::V(0x2), Register::V(0x3),
+    Register::V(0x4), Register::V(0x5), Register::V(0x6), Register::V(0x7),
+    Register::V(0x8), Register::V(0x9), Register::V(0xA), Register::V(0xB),
+    Register::V(0xC), Register::V(0xD), Register::V(0xE), Register::V(0xF),
+    Register::I, Register::Sp, Register::Delay, Register::Sound,
+];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum TraceFormat {
+    /// One human-readable line per instruction, see [`TraceRecord`]'s `Display`.
+    Text,
+    /// The compact format read back by [`read_trace`]: a `C8TR` header and version byte,
+    /// then per record the cycle (u64), PC and opcode (u16), a u32 mask of changed
+    /// registers (bits 0-15 V0-VF, 16 I, 17 SP, 18 DT, 19 ST, bit 30 for an operand and
+    /// bit 31 for a fault), the operand (u16) and the registers' new values, I as a u16 and
+    /// the rest as bytes. Everything is little-endian.
+    Binary,
+}
+
+/// Selects which instructions a [`Tracer`] records. The default records everything.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct TraceFilter {
+    addresses: Option<RangeInclusive<u16>>,
+    classes: u16,
+}
+
+impl TraceFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only records instructions located in `range`.
+    pub fn addresses(mut self, range: RangeInclusive<u16>) -> Self {
+        self.addresses = Some(range);
+        self
+    }
+
+    /// Only records opcodes whose high nibble is one of `classes`, e.g. `&[0xD]` for draws
+    /// or `&[0x2, 0x0]` for calls and returns.
+    pub fn classes(mut self, classes: &[u8]) -> Self {
+        self.classes = classes.iter().fold(0, |mask, class| mask | 1 << (class & 0x0F));
+        self
+    }
+
+    pub fn matches(&self, pc: u16, opcode: u16) -> bool {
+        self.addresses.as_ref().is_none_or(|range| range.contains(&pc))
+            && self.classes & 1 << (opcode >> 12) != 0
+    }
+}
+
+impl Default for TraceFilter {
+    fn default() -> Self {
+        Self { addresses: None, classes: 0xFFFF }
+    }
+}
+
+/// One executed instruction. PC changes are implied by the next record and not listed.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct TraceRecord {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    /// The second word of `F000 nnnn`.
+    pub operand: Option<u16>,
+    /// Registers the instruction changed, with their new values.
+    pub changes: Vec<(Register, u16)>,
+    /// The instruction raised an error instead of running, so it changed nothing.
+    pub fault: bool,
+}
+
+impl TraceRecord {
+    fn new(cycle: u64, pc: u16, opcode: u16, operand: Option<u16>, before: &Registers, after: &Registers) -> Self {
+        let changes = TRACED_REGISTERS.iter()
+            .filter(|register| register.read(before) != register.read(after))
+            .map(|register| (*register, register.read(after)))
+            .collect();
+        Self { cycle, pc, opcode, operand, changes, fault: false }
+    }
+
+    fn write_binary(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let mask = TRACED_REGISTERS.iter().enumerate()
+            .filter(|(_, register)| self.changes.iter().any(|(changed, _)| changed == *register))
+            .fold(0u32, |mask, (bit, _)| mask | 1 << bit);
+        let flags = if self.fault { FAULT_BIT } else { 0 } | if self.operand.is_some() { OPERAND_BIT } else { 0 };
+
+        out.write_all(&self.cycle.to_le_bytes())?;
+        out.write_all(&self.pc.to_le_bytes())?;
+        out.write_all(&self.opcode.to_le_bytes())?;
+        out.write_all(&(mask | flags).to_le_bytes())?;
+        if let Some(operand) = self.operand {
+            out.write_all(&operand.to_le_bytes())?;
+        }
+        for register in TRACED_REGISTERS {
+            let Some((_, value)) = self.changes.iter().find(|(changed, _)| *changed == register) else { continue };
+            match register {
+                Register::I => out.write_all(&value.to_le_bytes())?,
+                _ => out.write_all(&[*value as u8])?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `cycle PC opcode disassembly changes`, e.g. `      12 0206 7A01 ADD VA, 0x01         VA=03`,
+/// with `FAULT` in place of the changes for an instruction that faulted.
+impl Display for TraceRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>8} {:04X} {:04X} ", self.cycle, self.pc, self.opcode)?;
+        let instruction = disassemble(self.opcode, self.operand.unwrap_or(0));
+        if self.fault {
+            return write!(f, "{:<20} FAULT", instruction.to_string());
+        }
+        match self.changes.is_empty() {
+            true => write!(f, "{instruction}")?,
+            false => write!(f, "{:<20}", instruction.to_string())?,
+        }
+        for (register, value) in &self.changes {
+            match register {
+                Register::I => write!(f, " {register}={value:04X}")?,
+                _ => write!(f, " {register}={value:02X}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs an emulator while recording every executed instruction to a writer. Tracing is
+/// opt-in: stepping through a `Tracer` instead of calling [`Chip8Emulator::tick`] directly
+/// is all it takes, as `chip8-headless --trace` does. `Fx0A` polls that keep waiting for a
+/// key are not recorded.
+pub struct Tracer<W: Write> {
+    writer: W,
+    format: TraceFormat,
+    filter: TraceFilter,
+}
+
+impl<W: Write> Tracer<W> {
+    /// Writes the binary header straight away, so an empty trace still reads back.
+    pub fn new(mut writer: W, format: TraceFormat) -> Result<Self, Chip8Error> {
+        if format == TraceFormat::Binary {
+            writer.write_all(MAGIC)?;
+            writer.write_all(&[TRACE_VERSION])?;
+        }
+        Ok(Self { writer, format, filter: TraceFilter::default() })
+    }
+
+    pub fn with_filter(mut self, filter: TraceFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// [`Chip8Emulator::tick`], recording the instruction if it ran and passes the filter.
+    /// `00FD` is recorded too; ticks of an already halted emulator run nothing and aren't. An
+    /// instruction that faults is recorded as a fault before the error is returned.
+    pub fn tick(&mut self, emulator: &mut Chip8Emulator) -> Result<StepOutcome, Chip8Error> {
+        let was_halted = emulator.is_halted();
+        let before = emulator.registers();
+        let word_at = |addr: usize| emulator.memory_range(addr, 2).ok()
+            .map(|fetch| u16::from_be_bytes([emulator.memory[fetch.start], emulator.memory[fetch.start + 1]]));
+        let opcode = word_at(emulator.program_counter).unwrap_or_default();
+        let operand = word_at(emulator.program_counter + 2)
+            .filter(|next| disassemble(opcode, *next).size() == 4);
+
+        let outcome = match emulator.tick() {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                if self.filter.matches(before.pc, opcode) {
+                    let fault = TraceRecord { cycle: emulator.cycles + 1, pc: before.pc, opcode, operand, changes: Vec::new(), fault: true };
+                    self.write(&fault)?;
+                }
+                return Err(e);
+            }
+        };
+        let executed = match outcome {
+            StepOutcome::Executed => true,
+            StepOutcome::Halted => !was_halted,
+            _ => false,
+        };
+        if executed && self.filter.matches(before.pc, opcode) {
+            self.write(&TraceRecord::new(emulator.cycles, before.pc, opcode, operand, &before, &emulator.registers()))?;
+        }
+        Ok(outcome)
+    }
+
+    fn write(&mut self, record: &TraceRecord) -> Result<(), Chip8Error> {
+        match self.format {
+            TraceFormat::Text => writeln!(self.writer, "{record}")?,
+            TraceFormat::Binary => record.write_binary(&mut self.writer)?,
+        }
+        Ok(())
+    }
+
+    /// [`Chip8Emulator::run_frame`] with every instruction traced.
+    pub fn run_frame(&mut self, emulator: &mut Chip8Emulator, instructions_per_frame: usize) -> Result<(), Chip8Error> {
+        for _ in 0..instructions_per_frame {
+            if self.tick(emulator)? != StepOutcome::Executed { break; }
+        }
+        emulator.tick_timers();
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Chip8Error> {
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Parses a trace written in [`TraceFormat::Binary`].
+pub fn read_trace(trace: &[u8]) -> Result<Vec<TraceRecord>, Chip8Error> {
+    let mut input = TraceReader(trace.strip_prefix(MAGIC.as_slice()).ok_or(Chip8Error::InvalidTrace)?);
+    if input.u8()? != TRACE_VERSION { return Err(Chip8Error::InvalidTrace); }
+
+    let mut records = Vec::new();
+    while !input.0.is_empty() {
+        let cycle = u64::from_le_bytes(input.take(8)?.try_into().unwrap());
+        let pc = input.u16()?;
+        let opcode = input.u16()?;
+        let mask = u32::from_le_bytes(input.take(4)?.try_into().unwrap());
+        if (mask & !(FAULT_BIT | OPERAND_BIT)) >> TRACED_REGISTERS.len() != 0 { return Err(Chip8Error::InvalidTrace); }
+        let operand = if mask & OPERAND_BIT != 0 { Some(input.u16()?) } else { None };
+
+        let mut changes = Vec::new();
+        for (bit, register) in TRACED_REGISTERS.iter().enumerate() {
+            if mask & 1 << bit == 0 { continue; }
+            let value = match register {
+                Register::I => input.u16()?,
+                _ => input.u8()? as u16,
+            };
+            changes.push((*register, value));
+        }
+        records.push(TraceRecord { cycle, pc, opcode, operand, changes, fault: mask & FAULT_BIT != 0 });
+    }
+    Ok(records)
+}
+
+/// Converts a binary trace into the text format.
+pub fn export_text(trace: &[u8], mut out: impl Write) -> Result<(), Chip8Error> {
+    for record in read_trace(trace)? {
+        writeln!(out, "{record}")?;
+    }
+    Ok(())
+}
+
+struct TraceReader<'a>(&'a [u8]);
+
+impl<'a> TraceReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        if self.0.len() < len { return Err(Chip8Error::InvalidTrace); }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Chip8Error> { Ok(self.take(1)?[0]) }
+    fn u16(&mut self) -> Result<u16, Chip8Error> { Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap())) }
+}
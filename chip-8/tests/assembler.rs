@@ -0,0 +1,75 @@
+use chip_8::{assemble, disassemble, Chip8Emulator, Instruction, SymbolMap};
+
+#[test]
+fn labels_defines_and_data() {
+    let source = "
+        define SPEED 2
+        start:  LD I, ball      ; forward reference
+                ADD V0, SPEED
+                JP start
+        ball:   sprite .##.....
+                sprite ####....
+                db 0x60, %01100000, $F0, -1
+                dw 0x1234, start + 2
+        far:    LD I, LONG far
+    ";
+    let assembly = assemble(source).unwrap();
+    assert_eq!(assembly.bytes, [
+        0xA2, 0x06, 0x70, 0x02, 0x12, 0x00,
+        0x60, 0xF0,
+        0x60, 0x60, 0xF0, 0xFF,
+        0x12, 0x34, 0x02, 0x02,
+        0xF0, 0x00, 0x02, 0x10,
+    ]);
+    assert_eq!(assembly.symbols.address("ball"), Some(0x206));
+    assert_eq!(assembly.symbols.label_at(0x200), Some("start"));
+
+    let reparsed: SymbolMap = assembly.symbols.to_string().parse().unwrap();
+    assert_eq!(reparsed, assembly.symbols);
+    Chip8Emulator::new(&assembly.bytes);
+}
+
+#[test]
+fn accepts_disassembler_output() {
+    for opcode in 0..=u16::MAX {
+        let Some(instruction) = Instruction::decode(opcode, 0x1234) else { continue };
+
+        let (opcode, operand) = instruction.encode();
+        let expected: Vec<u8> = [Some(opcode), operand].into_iter().flatten().flat_map(u16::to_be_bytes).collect();
+        let assembly = assemble(&disassemble(opcode, operand.unwrap_or(0)).to_string()).unwrap();
+        assert_eq!(assembly.bytes, expected, "{instruction}");
+    }
+}
+
+#[test]
+fn reports_line_numbers() {
+    let error = assemble("CLS\nJP nowhere\n").unwrap_err();
+    assert_eq!((error.line, error.message.as_str()), (2, "undefined symbol `nowhere`"));
+
+    assert_eq!(assemble("LD V0, 256").unwrap_err().to_string(), "line 1: 256 does not fit in a byte");
+    assert_eq!(assemble("a:\na:").unwrap_err().to_string(), "line 2: `a` is already defined");
+    assert_eq!(assemble("CLS V0").unwrap_err().to_string(), "line 1: invalid operands for `CLS`");
+    assert_eq!(assemble("FOO").unwrap_err().to_string(), "line 1: unknown instruction `FOO`");
+    assert_eq!(assemble("JP 0x1000").unwrap_err().to_string(), "line 1: 4096 does not fit in an address");
+}
+
+#[test]
+fn defines_resolve_in_any_order() {
+    let source = "
+        define END start + SIZE
+        define SIZE 0X4
+        start:  LD I, END
+                JP start
+    ";
+    assert_eq!(assemble(source).unwrap().bytes, [0xA2, 0x04, 0x12, 0x00]);
+
+    let error = assemble("define FOO BAR\ndefine BAR FOO + 1\nCLS").unwrap_err();
+    assert_eq!(error.to_string(), "line 1: circular definition of `BAR`");
+    let error = assemble("define FOO BAR\ndefine BAR nowhere\nCLS").unwrap_err();
+    assert_eq!(error.to_string(), "line 2: undefined symbol `nowhere`");
+
+    for name in ["K", "dt", "ST", "I", "F"] {
+        let error = assemble(&format!("define {name} 1")).unwrap_err();
+        assert_eq!(error.message, format!("`{name}` is not a valid symbol name"));
+    }
+}
@@ -0,0 +1,255 @@
+//! Runs test ROMs for a fixed number of frames and compares the screen against golden images
+//! in `tests/golden`, one text row per display row: `.` for an unlit pixel, `#` for the first
+//! plane, `+` for the second and `@` for both. A difference is reported by the opcodes or
+//! quirks whose part of the screen changed.
+//!
+//! The goldens are the screens each ROM shows when everything passes, not captures from this
+//! emulator: "OK" in all 18 cells for test_opcode and "BON" for BC_test, as their authors
+//! document, and a tick in every cell for the programs in `roms/conformance`. Those check
+//! flags, quirks and the keypad the way the Timendus suite does, and are compiled from Octo
+//! source on each run. `UPDATE_GOLDEN=1` writes the current screens instead, for a new ROM
+//! whose pass screen has to be checked by eye before committing it.
+
+use std::path::Path;
+
+use chip_8::{compile_octo, Chip8Emulator, OctoTarget, Quirks};
+
+const INSTRUCTIONS_PER_FRAME: usize = 1000;
+
+/// A part of the screen showing the result for one opcode or quirk.
+struct Region {
+    label: &'static str,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+const fn region(label: &'static str, x: usize, y: usize, width: usize, height: usize) -> Region {
+    Region { label, x, y, width, height }
+}
+
+/// The 8x6 cells the conformance ROMs draw a mark in, eight to a row.
+const fn cells<const N: usize>(labels: [&'static str; N]) -> [Region; N] {
+    let mut regions = [const { region("", 0, 0, 0, 0) }; N];
+    let mut i = 0;
+    while i < N {
+        regions[i] = region(labels[i], i % 8 * 8, i / 8 * 6, 8, 6);
+        i += 1;
+    }
+    regions
+}
+
+struct Case {
+    name: &'static str,
+    /// A ROM image, or Octo source if it ends in `.8o`.
+    rom: &'static str,
+    quirks: Quirks,
+    frames: usize,
+    /// Bytes written to memory before running, e.g. to answer a ROM's menu up front.
+    pokes: &'static [(usize, u8)],
+    /// Keys pressed (`true`) or released before the given frame.
+    keys: &'static [(usize, u8, bool)],
+    /// Name of the golden image, when cases share one.
+    golden: Option<&'static str>,
+    regions: &'static [Region],
+}
+
+const CASE: Case = Case {
+    name: "",
+    rom: "",
+    quirks: Quirks::COSMAC_VIP,
+    frames: 10,
+    pokes: &[],
+    keys: &[],
+    golden: None,
+    regions: &[],
+};
+
+/// corax89's test_opcode lays its results out in three columns of six.
+const TEST_OPCODE_REGIONS: [Region; 18] = {
+    const LABELS: [[&str; 3]; 6] = [
+        ["3XNN", "00EE", "8XY5"],
+        ["4XNN", "8XY0", "8XY6"],
+        ["5XY0", "8XY1", "8XYE"],
+        ["7XNN", "8XY2", "FX55"],
+        ["9XY0", "8XY3", "FX33"],
+        ["ANNN", "8XY4", "FX1E"],
+    ];
+    const COLUMNS: [usize; 3] = [0, 22, 43];
+
+    let mut regions = [const { region("", 0, 0, 0, 0) }; 18];
+    let mut i = 0;
+    while i < 18 {
+        let (row, column) = (i / 3, i % 3);
+        regions[i] = region(LABELS[row][column], COLUMNS[column], row * 5, 21, 5);
+        i += 1;
+    }
+    regions
+};
+
+const FLAGS_REGIONS: [Region; 31] = cells([
+    "8XY1 value", "8XY1 VF reset", "8XY2 value", "8XY2 VF reset", "8XY3 value", "8XY3 VF reset",
+    "8XY4 value", "8XY4 VF", "8XY4 carry value", "8XY4 carry VF",
+    "8XY5 value", "8XY5 VF", "8XY5 borrow value", "8XY5 borrow VF",
+    "8XY7 value", "8XY7 VF", "8XY7 borrow value", "8XY7 borrow VF",
+    "8XY6 value", "8XY6 VF", "8XY6 even value", "8XY6 even VF",
+    "8XYE value", "8XYE VF", "8XYE small value", "8XYE small VF",
+    "8XY4 into VF", "8XY5 into VF", "8XY7 into VF", "8XY6 into VF", "8XYE into VF",
+]);
+
+const QUIRKS_REGIONS: [Region; 6] = cells(["vF reset", "memory", "shifting", "jumping", "wrap x", "wrap y"]);
+
+const KEYPAD_REGIONS: [Region; 4] = cells(["EX9E", "EXA1", "FX0A", "FX0A release"]);
+
+fn render(emulator: &Chip8Emulator) -> String {
+    emulator.framebuffer().to_string()
+}
+
+/// Labels of the regions where `actual` and `expected` differ, or the differing rows if the
+/// case has no regions or the difference lies outside all of them.
+fn failing_regions(case: &Case, actual: &[&str], expected: &[&str]) -> Vec<String> {
+    if actual.len() != expected.len() || actual.iter().zip(expected).any(|(a, e)| a.len() != e.len()) {
+        return vec!["screen size".to_string()];
+    }
+
+    let differs = |x: usize, y: usize| actual[y].as_bytes().get(x) != expected[y].as_bytes().get(x);
+    let mut failing: Vec<String> = case.regions.iter()
+        .filter(|r| (r.y..r.y + r.height).any(|y| (r.x..r.x + r.width).any(|x| differs(x, y))))
+        .map(|r| r.label.to_string())
+        .collect();
+
+    if failing.is_empty() {
+        let rows: Vec<usize> = (0..actual.len()).filter(|y| actual[*y] != expected[*y]).collect();
+        failing.push(format!("rows {}-{}", rows[0], rows[rows.len() - 1]));
+    }
+    failing
+}
+
+fn load(case: &Case) -> Vec<u8> {
+    let rom_path = Path::new("./roms").join(case.rom);
+    if rom_path.extension().is_some_and(|extension| extension == "8o") {
+        let source = std::fs::read_to_string(&rom_path)
+            .unwrap_or_else(|e| panic!("cannot read {}: {e}", rom_path.display()));
+        return compile_octo(&source, OctoTarget::Chip8)
+            .unwrap_or_else(|e| panic!("{} does not compile: {e}", rom_path.display()))
+            .bytes;
+    }
+    std::fs::read(&rom_path).unwrap_or_else(|e| panic!("cannot read {}: {e}", rom_path.display()))
+}
+
+fn check(case: &Case) {
+    let mut emulator = Chip8Emulator::with_quirks(&load(case), case.quirks);
+    for (address, value) in case.pokes {
+        emulator.write_memory(*address, *value).unwrap();
+    }
+    for frame in 0..case.frames {
+        if emulator.is_halted() { break }
+        for (_, key, pressed) in case.keys.iter().filter(|(at, ..)| *at == frame) {
+            emulator.set_key(*key, *pressed);
+        }
+        emulator.run_frame(INSTRUCTIONS_PER_FRAME)
+            .unwrap_or_else(|e| panic!("{} faulted: {e}\n{}", case.name, render(&emulator)));
+    }
+
+    let actual = render(&emulator);
+    let golden_path = Path::new("./tests/golden").join(format!("{}.txt", case.golden.unwrap_or(case.name)));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&golden_path, &actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&golden_path)
+        .unwrap_or_else(|_| panic!("no golden image for {} at {}", case.name, golden_path.display()));
+    // Compared line by line, so a checkout with CRLF line endings still matches
+    let (actual_lines, expected_lines): (Vec<&str>, Vec<&str>) = (actual.lines().collect(), expected.lines().collect());
+    if actual_lines != expected_lines {
+        panic!(
+            "{} differs from its golden image in: {}\n--- expected\n{expected}--- actual\n{actual}",
+            case.name,
+            failing_regions(case, &actual_lines, &expected_lines).join(", "),
+        );
+    }
+}
+
+/// BC_test shifts Vx in place and expects `Fx55` / `Fx65` to leave I alone, as SUPER-CHIP does.
+#[test]
+fn bc_test() {
+    check(&Case { name: "bc_test", rom: "BC_test.ch8", quirks: Quirks::SUPER_CHIP, ..CASE });
+}
+
+#[test]
+fn test_opcode() {
+    check(&Case { name: "test_opcode", rom: "test_opcode.ch8", regions: &TEST_OPCODE_REGIONS, ..CASE });
+}
+
+#[test]
+fn flags() {
+    check(&Case { name: "flags", rom: "conformance/flags.8o", regions: &FLAGS_REGIONS, ..CASE });
+}
+
+/// The quirks ROM reads what to expect from 0x1F8: VF after `8xy1`, the byte I points at
+/// after saving 0x10 and 0x11 in front of 0x12, 4 shifted right in place or 16 shifted into
+/// it, whether `Bnnn` added V2, and whether sprites wrapped horizontally and vertically.
+#[test]
+fn quirks() {
+    const fn expect(values: [u8; 6]) -> [(usize, u8); 6] {
+        let mut pokes = [(0, 0); 6];
+        let mut i = 0;
+        while i < 6 {
+            pokes[i] = (0x1F8 + i, values[i]);
+            i += 1;
+        }
+        pokes
+    }
+    const QUIRKS: Case = Case { rom: "conformance/quirks.8o", golden: Some("quirks"), regions: &QUIRKS_REGIONS, ..CASE };
+    static PLATFORMS: [Case; 4] = [
+        Case { name: "quirks (COSMAC VIP)", quirks: Quirks::COSMAC_VIP, pokes: &expect([0, 0x12, 8, 0, 0, 0]), ..QUIRKS },
+        Case { name: "quirks (CHIP-48)", quirks: Quirks::CHIP_48, pokes: &expect([1, 0x11, 2, 1, 0, 0]), ..QUIRKS },
+        Case { name: "quirks (SUPER-CHIP)", quirks: Quirks::SUPER_CHIP, pokes: &expect([1, 0x10, 2, 1, 0, 0]), ..QUIRKS },
+        Case { name: "quirks (XO-CHIP)", quirks: Quirks::XO_CHIP, pokes: &expect([1, 0x12, 8, 0, 1, 1]), ..QUIRKS },
+    ];
+    PLATFORMS.iter().for_each(check);
+}
+
+/// Key 5 is held from the start; A goes down while `Fx0A` waits and comes back up later.
+#[test]
+fn keypad() {
+    check(&Case {
+        name: "keypad",
+        rom: "conformance/keypad.8o",
+        frames: 20,
+        keys: &[(0, 5, true), (5, 0xA, true), (10, 0xA, false)],
+        regions: &KEYPAD_REGIONS,
+        ..CASE
+    });
+}
+
+#[test]
+fn arithmetic_flags() {
+    use chip_8::Instruction::*;
+
+    let mut emulator = Chip8Emulator::new(&[0x00, 0xE0]);
+    let mut run = |instructions: &[chip_8::Instruction]| {
+        for instruction in instructions {
+            emulator.execute(*instruction).unwrap();
+        }
+        emulator.registers().v
+    };
+
+    // Vx - Vy with equal operands does not borrow
+    let v = run(&[SetByte { x: 0, kk: 5 }, SetByte { x: 1, kk: 5 }, SubXY { x: 0, y: 1 }]);
+    assert_eq!((v[0], v[0xF]), (0, 1));
+
+    // 8xy7 computes Vy - Vx
+    let v = run(&[SetByte { x: 0, kk: 3 }, SetByte { x: 1, kk: 10 }, SubnXY { x: 0, y: 1 }]);
+    assert_eq!((v[0], v[0xF]), (7, 1));
+    let v = run(&[SetByte { x: 0, kk: 10 }, SetByte { x: 1, kk: 3 }, SubnXY { x: 0, y: 1 }]);
+    assert_eq!((v[0], v[0xF]), (249, 0));
+
+    // With VF as the destination the flag wins over the result
+    let v = run(&[SetByte { x: 0xF, kk: 0xFF }, SetByte { x: 1, kk: 2 }, AddXY { x: 0xF, y: 1 }]);
+    assert_eq!(v[0xF], 1);
+    let v = run(&[SetByte { x: 0xF, kk: 1 }, SetByte { x: 1, kk: 2 }, SubXY { x: 0xF, y: 1 }]);
+    assert_eq!(v[0xF], 0);
+}
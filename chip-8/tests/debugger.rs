@@ -0,0 +1,74 @@
+use chip_8::{Breakpoint, Chip8Emulator, Debugger, RegisterCondition, StopReason};
+
+// 0x200: v0 := 0
+// 0x202: v0 += 1
+// 0x204: i := 0x300
+// 0x206: save v0
+// 0x208: sprite v0 v0 1
+// 0x20A: jump 0x202
+const COUNTER: [u8; 12] = [0x60, 0x00, 0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0xD0, 0x01, 0x12, 0x02];
+
+#[test]
+fn pc_and_opcode_breakpoints() {
+    let mut emulator = Chip8Emulator::new(&COUNTER);
+    let mut debugger = Debugger::new();
+    let at_save = debugger.add(Breakpoint::Pc(0x206));
+    let on_draw = debugger.add(Breakpoint::opcode_pattern("Dxyn").unwrap());
+
+    assert_eq!(emulator.run_until_break(&debugger, 100), Ok(StopReason::Breakpoint(at_save)));
+    assert_eq!(emulator.registers().pc, 0x206);
+    assert_eq!(emulator.run_until_break(&debugger, 100), Ok(StopReason::Breakpoint(on_draw)));
+    assert_eq!(emulator.registers().pc, 0x208);
+
+    debugger.clear();
+    assert_eq!(emulator.run_until_break(&debugger, 100), Ok(StopReason::LimitReached));
+}
+
+#[test]
+fn memory_and_register_watchpoints() {
+    let mut emulator = Chip8Emulator::new(&COUNTER);
+    let mut debugger = Debugger::new();
+    let write = debugger.add(Breakpoint::MemoryWrite(0x300..=0x300));
+    assert_eq!(emulator.run_until_break(&debugger, 100), Ok(StopReason::Breakpoint(write)));
+    assert_eq!(emulator.last_memory_write(), Some(0x300..0x301));
+
+    debugger.clear();
+    // Fx55 has left I one past the saved register
+    let read = debugger.add(Breakpoint::MemoryRead(0x301..=0x310));
+    assert_eq!(emulator.run_until_break(&debugger, 100), Ok(StopReason::Breakpoint(read)));
+    assert_eq!(emulator.registers().pc, 0x20A);
+
+    debugger.clear();
+    let condition = RegisterCondition::parse("V0 == 0x10").unwrap();
+    let counter = debugger.add(Breakpoint::Register(condition));
+    assert_eq!(emulator.run_until_break(&debugger, 1000), Ok(StopReason::Breakpoint(counter)));
+    assert_eq!(emulator.registers().v[0], 0x10);
+    assert_eq!(emulator.run_until_break(&debugger, 1000), Ok(StopReason::LimitReached));
+
+    assert!(RegisterCondition::parse("I > 0xF00").is_some());
+    assert!(RegisterCondition::parse("V3 =< 1").is_none());
+}
+
+#[test]
+fn step_over_and_out() {
+    // 0x200: call 0x206; 0x202: v1 := 1; 0x204: jump 0x204
+    // 0x206: v0 := 5; 0x208: return
+    let mut emulator = Chip8Emulator::new(&[0x22, 0x06, 0x61, 0x01, 0x12, 0x04, 0x60, 0x05, 0x00, 0xEE]);
+    let debugger = Debugger::new();
+
+    let depth = emulator.registers().sp;
+    assert_eq!(emulator.run_until(&debugger, 100, |r| r.sp <= depth), Ok(StopReason::Finished));
+    assert_eq!(emulator.registers().pc, 0x202);
+    assert_eq!(emulator.registers().v[0], 5);
+
+    let mut emulator = Chip8Emulator::new(&[0x22, 0x06, 0x61, 0x01, 0x12, 0x04, 0x60, 0x05, 0x00, 0xEE]);
+    emulator.tick().unwrap();
+    assert_eq!(emulator.stack(), [0x202]);
+    let depth = emulator.registers().sp - 1;
+    assert_eq!(emulator.run_until(&debugger, 100, |r| r.sp <= depth), Ok(StopReason::Finished));
+    assert_eq!(emulator.registers().pc, 0x202);
+
+    emulator.write_memory(0x202, 0x62).unwrap();
+    assert_eq!(emulator.memory()[0x202], 0x62);
+    assert!(emulator.write_memory(0x1000, 0).is_err());
+}
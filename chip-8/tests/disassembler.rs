@@ -0,0 +1,22 @@
+use chip_8::{disassemble, disassemble_rom, Instruction};
+
+#[test]
+fn cowgod_mnemonics() {
+    assert_eq!(disassemble(0x631F, 0).to_string(), "LD V3, 0x1F");
+    assert_eq!(disassemble(0xD015, 0).to_string(), "DRW V0, V1, 5");
+    assert_eq!(disassemble(0xEA9E, 0).to_string(), "SKP VA");
+    assert_eq!(disassemble(0x8AB6, 0), Instruction::ShrXY { x: 0xA, y: 0xB });
+    assert_eq!(disassemble(0x5121, 0), Instruction::Data(0x5121));
+}
+
+#[test]
+fn listing_with_addresses() {
+    let lines = disassemble_rom(&[0x00, 0xE0, 0xF0, 0x00, 0x12, 0x34, 0x12, 0x00, 0xFF], 0x200);
+    let text = lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+    assert_eq!(text, [
+        "0x200: 00E0       CLS",
+        "0x202: F000 1234  LD I, LONG 0x1234",
+        "0x206: 1200       JP 0x200",
+        "0x208: FF00       DW 0xFF00",
+    ]);
+}
@@ -0,0 +1,38 @@
+use chip_8::{Chip8Emulator, Chip8Error, Instruction, StepOutcome};
+
+#[test]
+fn return_with_empty_stack() {
+    let mut emulator = Chip8Emulator::new(&[0x00, 0xEE]);
+    assert_eq!(emulator.tick(), Err(Chip8Error::StackUnderflow { pc: 0x200 }));
+}
+
+#[test]
+fn recursive_call_overflows() {
+    let mut emulator = Chip8Emulator::new(&[0x22, 0x00]);
+    for _ in 0..16 {
+        assert_eq!(emulator.tick(), Ok(StepOutcome::Executed));
+    }
+    assert_eq!(emulator.tick(), Err(Chip8Error::StackOverflow { pc: 0x200 }));
+}
+
+#[test]
+fn unknown_opcode() {
+    let mut emulator = Chip8Emulator::new(&[0xF0, 0xFF]);
+    assert_eq!(emulator.tick(), Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0xF0FF }));
+}
+
+#[test]
+fn execute_decoded_instructions() {
+    let mut emulator = Chip8Emulator::new(&[0x00, 0xE0]);
+    assert_eq!(Instruction::decode(0x00FD, 0), Some(Instruction::Exit));
+    assert_eq!(emulator.execute(Instruction::Data(0x5121)), Err(Chip8Error::UnknownOpcode { pc: 0x1FE, opcode: 0x5121 }));
+    assert_eq!(emulator.execute(Instruction::Exit), Ok(StepOutcome::Halted));
+}
+
+#[test]
+fn read_past_end_of_memory() {
+    // I := 0xFFF; load v0 - v2
+    let mut emulator = Chip8Emulator::new(&[0xAF, 0xFF, 0xF2, 0x65]);
+    emulator.tick().unwrap();
+    assert_eq!(emulator.tick(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000 }));
+}
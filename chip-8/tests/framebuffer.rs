@@ -0,0 +1,106 @@
+use chip_8::{Chip8Emulator, DirtyRect, Quirks};
+
+// i := 0x20A; v0 := 10; v1 := 5; sprite v0 v1 2; jump 0x208; <sprite 0xF0 0x90>
+const SPRITE: [u8; 12] = [0xA2, 0x0A, 0x60, 0x0A, 0x61, 0x05, 0xD0, 0x12, 0x12, 0x08, 0xF0, 0x90];
+
+#[test]
+fn draw_marks_sprite_area_dirty() {
+    let mut emulator = Chip8Emulator::new(&SPRITE);
+    // A new screen has to be drawn in full
+    assert_eq!(emulator.take_dirty(), Some(DirtyRect { x: 0, y: 0, width: 64, height: 32 }));
+    assert_eq!(emulator.take_dirty(), None);
+
+    for _ in 0..4 {
+        emulator.tick().unwrap();
+    }
+    assert_eq!(emulator.take_dirty(), Some(DirtyRect { x: 10, y: 5, width: 4, height: 2 }));
+
+    let framebuffer = emulator.framebuffer();
+    assert_eq!((framebuffer.pixel(10, 5), framebuffer.pixel(11, 6), framebuffer.pixel(13, 6)), (1, 0, 1));
+    let row = framebuffer.rows().nth(5).unwrap();
+    assert_eq!(row.plane(0), 0xF << (128 - 14));
+    assert_eq!(row.pixels().filter(|pixel| *pixel != 0).count(), 4);
+}
+
+#[test]
+fn scrolling_moves_packed_rows() {
+    // hires; v0 := 120; sprite v0 v1 2; scroll-right; scroll-down 1
+    let rom = [0x00, 0xFF, 0x60, 0x78, 0xA2, 0x0C, 0xD0, 0x12, 0x00, 0xFB, 0x00, 0xC1, 0xF0, 0x90];
+    let mut emulator = Chip8Emulator::with_quirks(&rom, Quirks::SUPER_CHIP);
+    for _ in 0..4 {
+        emulator.tick().unwrap();
+    }
+    assert_eq!(emulator.framebuffer().width(), 128);
+    emulator.take_dirty();
+
+    emulator.tick().unwrap();
+    emulator.tick().unwrap();
+    let framebuffer = emulator.framebuffer();
+    assert_eq!(framebuffer.row(0).plane(0), 0);
+    assert_eq!(framebuffer.row(1).plane(0), 0b1111);
+    assert_eq!(framebuffer.row(2).plane(0), 0b1001);
+    assert_eq!(emulator.take_dirty(), Some(DirtyRect { x: 0, y: 0, width: 128, height: 64 }));
+}
+
+#[test]
+fn resolution_change_clears_per_quirk() {
+    // i := 0x206; sprite v0 v0 1; hires; <sprite 0x80>
+    let rom = [0xA2, 0x06, 0xD0, 0x01, 0x00, 0xFF, 0x80];
+    let switch = |quirks: Quirks| {
+        let mut emulator = Chip8Emulator::with_quirks(&rom, quirks);
+        for _ in 0..3 {
+            emulator.tick().unwrap();
+        }
+        assert_eq!(emulator.framebuffer().width(), 128);
+        assert_eq!(emulator.take_dirty(), Some(DirtyRect { x: 0, y: 0, width: 128, height: 64 }));
+        emulator.framebuffer().pixel(0, 0)
+    };
+
+    assert_eq!(switch(Quirks::SUPER_CHIP), 1);
+    assert_eq!(switch(Quirks::XO_CHIP), 0);
+}
+
+/// Draws an 8x2 sprite of solid rows at (60, 31), hanging off the right and bottom edges.
+fn draw_at_corner(quirks: Quirks) -> Chip8Emulator {
+    // v0 := 60; v1 := 31; i := 0x20C; sprite v0 v1 2; jump 0x20A; <sprite 0xFF 0xFF>
+    let rom = [0x60, 0x3C, 0x61, 0x1F, 0xA2, 0x0C, 0xD0, 0x12, 0x12, 0x08, 0x12, 0x0A, 0xFF, 0xFF];
+    let mut emulator = Chip8Emulator::with_quirks(&rom, quirks);
+    for _ in 0..4 {
+        emulator.tick().unwrap();
+    }
+    emulator
+}
+
+#[test]
+fn sprites_clip_or_wrap_per_axis() {
+    let lit = |emulator: &Chip8Emulator| emulator.framebuffer().rows().map(|row| row.pixels().filter(|p| *p != 0).count()).sum::<usize>();
+
+    let clipped = draw_at_corner(Quirks::COSMAC_VIP);
+    assert_eq!(lit(&clipped), 4);
+    assert_eq!((clipped.framebuffer().pixel(63, 31), clipped.framebuffer().pixel(0, 31)), (1, 0));
+
+    let wrapped = draw_at_corner(Quirks { wrap_sprites_x: true, ..Quirks::COSMAC_VIP });
+    assert_eq!(lit(&wrapped), 8);
+    assert_eq!((wrapped.framebuffer().pixel(3, 31), wrapped.framebuffer().pixel(4, 31), wrapped.framebuffer().pixel(60, 0)), (1, 0, 0));
+
+    let wrapped = draw_at_corner(Quirks { wrap_sprites_y: true, ..Quirks::COSMAC_VIP });
+    assert_eq!(lit(&wrapped), 8);
+    assert_eq!((wrapped.framebuffer().pixel(60, 0), wrapped.framebuffer().pixel(0, 0)), (1, 0));
+
+    assert_eq!(lit(&draw_at_corner(Quirks::XO_CHIP)), 16);
+}
+
+#[test]
+fn sprite_data_wraps_around_memory() {
+    // i := 0xFFF; sprite v0 v0 2
+    let mut emulator = Chip8Emulator::new(&[0xAF, 0xFF, 0xD0, 0x02]);
+    emulator.write_memory(0xFFF, 0x80).unwrap();
+    emulator.tick().unwrap();
+    emulator.tick().unwrap();
+
+    // The second row comes from address 0, the top of the font's "0"
+    assert_eq!(emulator.last_memory_read(), Some(0xFFF..0x1000));
+    let framebuffer = emulator.framebuffer();
+    assert_eq!((framebuffer.pixel(0, 0), framebuffer.pixel(1, 0)), (1, 0));
+    assert_eq!((0..8).map(|x| framebuffer.pixel(x, 1)).collect::<Vec<_>>(), [1, 1, 1, 1, 0, 0, 0, 0]);
+}
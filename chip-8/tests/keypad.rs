@@ -0,0 +1,57 @@
+use chip_8::{Chip8Emulator, Quirks, StepOutcome};
+
+// 0x200: v0 := key
+// 0x202: v1 := key
+// 0x204: jump 0x204
+const TWO_KEYS: [u8; 6] = [0xF0, 0x0A, 0xF1, 0x0A, 0x12, 0x04];
+
+#[test]
+fn wait_key_on_release() {
+    let mut emulator = Chip8Emulator::with_quirks(&TWO_KEYS, Quirks::COSMAC_VIP);
+    emulator.press_key(0xA);
+    assert_eq!(emulator.tick(), Ok(StepOutcome::WaitingForKey));
+    assert_eq!(emulator.tick(), Ok(StepOutcome::WaitingForKey));
+
+    emulator.release_key(0xA);
+    assert!(!emulator.is_key_pressed(0xA));
+    assert_eq!(emulator.tick(), Ok(StepOutcome::Executed));
+    assert_eq!(emulator.registers().v[0], 0xA);
+
+    // The same keystroke doesn't also answer the second wait
+    assert_eq!(emulator.tick(), Ok(StepOutcome::WaitingForKey));
+    emulator.press_key(0x3);
+    assert_eq!(emulator.tick(), Ok(StepOutcome::WaitingForKey));
+    emulator.release_key(0x3);
+    assert_eq!(emulator.tick(), Ok(StepOutcome::Executed));
+    assert_eq!(emulator.registers().v[1], 0x3);
+}
+
+#[test]
+fn wait_key_on_press() {
+    let mut emulator = Chip8Emulator::with_quirks(&TWO_KEYS, Quirks::SUPER_CHIP);
+    emulator.press_key(0x5);
+    assert_eq!(emulator.tick(), Ok(StepOutcome::WaitingForKey));
+
+    // Holding a key is not a new press
+    emulator.press_key(0x5);
+    assert_eq!(emulator.tick(), Ok(StepOutcome::WaitingForKey));
+
+    emulator.press_key(0x7);
+    assert!(emulator.is_key_pressed(0x7));
+    assert_eq!(emulator.tick(), Ok(StepOutcome::Executed));
+    assert_eq!(emulator.registers().v[0], 0x7);
+    assert_eq!(emulator.tick(), Ok(StepOutcome::WaitingForKey));
+}
+
+#[test]
+fn key_wait_survives_save_state() {
+    let mut emulator = Chip8Emulator::new(&TWO_KEYS);
+    emulator.press_key(0x1);
+    emulator.tick().unwrap();
+    emulator.release_key(0x1);
+
+    let mut restored = Chip8Emulator::default();
+    restored.load_state(&emulator.save_state()).unwrap();
+    assert_eq!(restored.tick(), Ok(StepOutcome::Executed));
+    assert_eq!(restored.registers().v[0], 0x1);
+}
@@ -0,0 +1,30 @@
+use chip_8::{Chip8Emulator, Chip8Error};
+
+#[test]
+fn rejects_empty_rom() {
+    assert_eq!(Chip8Emulator::try_new(&[]).err(), Some(Chip8Error::EmptyRom));
+}
+
+#[test]
+fn rejects_oversized_rom() {
+    let rom = vec![0; 3585];
+    assert_eq!(Chip8Emulator::try_new(&rom).err(), Some(Chip8Error::RomTooLarge { size: 3585, max: 3584 }));
+}
+
+#[test]
+fn loads_from_reader_and_path() {
+    let from_path = Chip8Emulator::from_path("./roms/BC_test.ch8").unwrap();
+    let file = std::fs::File::open("./roms/BC_test.ch8").unwrap();
+    let from_reader = Chip8Emulator::from_reader(file).unwrap();
+    assert_eq!(format!("{from_path}"), format!("{from_reader}"));
+
+    let missing = Chip8Emulator::from_path("./roms/does-not-exist.ch8");
+    assert_eq!(missing.err(), Some(Chip8Error::Io(std::io::ErrorKind::NotFound)));
+}
+
+#[test]
+fn loads_at_custom_address() {
+    let mut emulator = Chip8Emulator::default();
+    emulator.load_rom_at(&[0x00, 0xEE], 0x600).unwrap();
+    assert_eq!(emulator.tick(), Err(Chip8Error::StackUnderflow { pc: 0x600 }));
+}
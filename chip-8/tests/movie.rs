@@ -0,0 +1,71 @@
+use chip_8::{Chip8Emulator, Chip8Error, Movie, Quirks, Rng};
+
+// 0x200: v0 := key
+// 0x202: v1 := random 0xFF
+// 0x204: v2 += v1
+// 0x206: jump 0x200
+const KEYS_AND_RANDOM: [u8; 8] = [0xF0, 0x0A, 0xC1, 0xFF, 0x82, 0x14, 0x12, 0x00];
+
+/// Presses key 3 for two frames out of every five.
+fn record(emulator: &mut Chip8Emulator, frames: usize) -> Movie {
+    let mut movie = Movie::record(emulator, &KEYS_AND_RANDOM, 10).unwrap();
+    for frame in 0..frames {
+        emulator.set_key(0x3, frame % 5 < 2);
+        movie.record_frame(emulator).unwrap();
+    }
+    movie.finish(emulator);
+    movie
+}
+
+fn play(movie: &Movie, emulator: &mut Chip8Emulator) -> Result<(), Chip8Error> {
+    movie.start_playback(emulator, &KEYS_AND_RANDOM)?;
+    let mut frame = 0;
+    while movie.play_frame(emulator, frame)? {
+        frame += 1;
+    }
+    movie.verify(emulator)
+}
+
+#[test]
+fn playback_matches_recording() {
+    let mut emulator = Chip8Emulator::default();
+    emulator.set_quirks(Quirks::COSMAC_VIP);
+    emulator.set_rng(Rng::seeded(77));
+    let movie = record(&mut emulator, 60);
+    assert_eq!(movie.frames.len(), 60);
+
+    // Playback restores the quirks and generator, whatever the emulator had before
+    let mut other = Chip8Emulator::default();
+    other.set_rng(Rng::seeded(1));
+    play(&movie, &mut other).unwrap();
+    assert_eq!(other.state_hash(), emulator.state_hash());
+    assert_eq!(other.registers().v, emulator.registers().v);
+}
+
+#[test]
+fn desync_and_wrong_rom_are_reported() {
+    let mut emulator = Chip8Emulator::default();
+    let mut movie = record(&mut emulator, 30);
+
+    movie.frames[13] ^= 1 << 0x3;
+    assert!(matches!(play(&movie, &mut Chip8Emulator::default()), Err(Chip8Error::MovieDesync { .. })));
+
+    assert_eq!(movie.start_playback(&mut emulator, &[0x12, 0x00]), Err(Chip8Error::MovieRomMismatch));
+}
+
+#[test]
+fn movie_round_trips_through_bytes() {
+    let mut emulator = Chip8Emulator::default();
+    emulator.set_rng(Rng::cosmac_vip(0x1234));
+    let movie = record(&mut emulator, 20);
+
+    let bytes = movie.to_bytes();
+    let loaded = Movie::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded.frames, movie.frames);
+    assert_eq!(loaded.instructions_per_frame, 10);
+    assert_eq!(loaded.final_state_hash, movie.final_state_hash);
+    play(&loaded, &mut Chip8Emulator::default()).unwrap();
+
+    assert_eq!(Movie::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(), Chip8Error::InvalidMovie);
+    assert_eq!(Movie::from_bytes(b"C8ST").unwrap_err(), Chip8Error::InvalidMovie);
+}
@@ -0,0 +1,134 @@
+use chip_8::{compile_octo, Chip8Emulator, OctoTarget};
+
+#[test]
+fn compiles_control_flow_and_data() {
+    let source = "
+        :const SPEED 2
+        :alias x v3
+        :calc DOUBLE { 1 + SPEED * 2 }
+        :macro bump reg amount { reg += amount }
+
+        : main
+            i := ball          # forward reference
+            x := 0
+            loop
+                bump x DOUBLE
+                while x != 20
+                if x key then x := SPEED
+                if x < 5 begin
+                    sprite x x 2
+                else
+                    draw
+                end
+            again
+        : draw  ;
+        : ball  0b01100000 0xF0
+    ";
+    let assembly = compile_octo(source, OctoTarget::Chip8).unwrap();
+    assert_eq!(assembly.bytes, [
+        0xA2, 0x20,             // i := ball
+        0x63, 0x00,             // x := 0
+        0x73, 0x05,             // loop: bump x DOUBLE
+        0x43, 0x14, 0x12, 0x1E, // while x != 20
+        0xE3, 0xA1, 0x63, 0x02, // if x key then x := SPEED
+        0x6F, 0x05, 0x8F, 0x37, // vf := 5, vf =- x
+        0x3F, 0x00, 0x12, 0x1A, // skip the jump to else when x < 5
+        0xD3, 0x32, 0x12, 0x1C, // sprite, jump to end
+        0x22, 0x1E,             // else: draw
+        0x12, 0x04,             // again
+        0x00, 0xEE,             // : draw ;
+        0x60, 0xF0,             // : ball
+    ]);
+    assert_eq!(assembly.symbols.address("draw"), Some(0x21E));
+    Chip8Emulator::with_quirks(&assembly.bytes, OctoTarget::Chip8.quirks());
+}
+
+#[test]
+fn jumps_to_main_when_it_is_not_first() {
+    let assembly = compile_octo(": helper ; : main helper", OctoTarget::Chip8).unwrap();
+    assert_eq!(assembly.bytes, [0x12, 0x04, 0x00, 0xEE, 0x22, 0x02]);
+}
+
+#[test]
+fn jump_to_main_stays_at_program_start() {
+    let assembly = compile_octo(":org 0x204 : helper ; : main helper", OctoTarget::Chip8).unwrap();
+    assert_eq!(assembly.bytes, [0x12, 0x06, 0x00, 0x00, 0x00, 0xEE, 0x22, 0x04]);
+
+    let assembly = compile_octo(":org 0x204 : main clear", OctoTarget::Chip8).unwrap();
+    assert_eq!(assembly.bytes, [0x12, 0x04, 0x00, 0x00, 0x00, 0xE0]);
+}
+
+#[test]
+fn compiles_data_directives() {
+    let source = "
+        : main
+            :unpack 0xA data
+            :byte { 1 + 2 }
+            :byte 0xFF
+        : data 0x12
+    ";
+    let assembly = compile_octo(source, OctoTarget::Chip8).unwrap();
+    assert_eq!(assembly.bytes, [
+        0x60, 0xA2, 0x61, 0x06, // :unpack 0xA data
+        0x03,                   // :byte { 1 + 2 }
+        0xFF,                   // :byte 0xFF
+        0x12,                   // : data
+    ]);
+}
+
+#[test]
+fn calc_groups_from_the_right() {
+    let source = "
+        :calc A { 2 * 3 + 1 }
+        :calc B { ( 2 * 3 ) + 1 }
+        :calc C { 7 / 2 }
+        :calc D { C * 2 }
+        : main
+            :byte A
+            :byte B
+            :byte D
+            :byte { 10 - 2 - 1 }
+    ";
+    let assembly = compile_octo(source, OctoTarget::Chip8).unwrap();
+    assert_eq!(assembly.bytes, [8, 7, 7, 9]);
+}
+
+#[test]
+fn loop_before_main_comes_back_to_itself() {
+    let assembly = compile_octo("loop v0 += 1 again : main clear", OctoTarget::Chip8).unwrap();
+    assert_eq!(assembly.bytes, [
+        0x12, 0x06, // jump main
+        0x70, 0x01, // loop: v0 += 1
+        0x12, 0x02, // again
+        0x00, 0xE0, // : main clear
+    ]);
+}
+
+#[test]
+fn compares_registers() {
+    let source = "
+        if v1 > v2 then v3 := 1
+        if v1 <= v2 then v3 := 2
+        if v1 >= v2 then v3 := 3
+    ";
+    let assembly = compile_octo(source, OctoTarget::Chip8).unwrap();
+    assert_eq!(assembly.bytes, [
+        0x8F, 0x20, 0x8F, 0x15, 0x4F, 0x00, 0x63, 0x01, // vf := v2, vf -= v1, skip unless borrowed
+        0x8F, 0x20, 0x8F, 0x15, 0x4F, 0x01, 0x63, 0x02, // vf := v2, vf -= v1, skip if borrowed
+        0x8F, 0x10, 0x8F, 0x25, 0x4F, 0x01, 0x63, 0x03, // vf := v1, vf -= v2, skip if borrowed
+    ]);
+}
+
+#[test]
+fn checks_target_and_names() {
+    let error = compile_octo("clear\nhires", OctoTarget::Chip8).unwrap_err();
+    assert_eq!(error.to_string(), "line 2: `hires` needs SCHIP but the target is CHIP-8");
+    assert!(compile_octo("i := long 0x1234 plane 3 audio", OctoTarget::XoChip).is_ok());
+
+    let error = compile_octo("jump nowhere", OctoTarget::Chip8).unwrap_err();
+    assert_eq!(error.to_string(), "line 1: undefined label `nowhere`");
+    let error = compile_octo("loop\nclear", OctoTarget::Chip8).unwrap_err();
+    assert_eq!(error.to_string(), "line 1: `loop` is never closed by `again`");
+    let error = compile_octo("if v0 == 1 begin\nclear\nelse\nclear\nelse\nend", OctoTarget::Chip8).unwrap_err();
+    assert_eq!(error.to_string(), "line 5: `begin` already has an `else`");
+}
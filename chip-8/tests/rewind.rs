@@ -0,0 +1,58 @@
+use chip_8::{Chip8Emulator, Chip8Error, RandomSource, RewindBuffer, Rng};
+
+#[test]
+fn rewinds_to_earlier_frames() {
+    let test = std::fs::read("./roms/BC_test.ch8").unwrap();
+    let mut emulator = Chip8Emulator::new(test.as_slice());
+    let mut rewind = RewindBuffer::new(8, 2);
+    let mut history = Vec::new();
+
+    for _ in 0..20 {
+        emulator.run_frame(10).unwrap();
+        rewind.record(&emulator);
+        history.push(emulator.save_state());
+    }
+    assert_eq!(rewind.available_frames(), 16);
+
+    assert_eq!(rewind.rewind(&mut emulator, 3), Ok(4));
+    assert_eq!(emulator.save_state(), history[14]);
+
+    assert_eq!(rewind.rewind(&mut emulator, 100), Ok(12));
+    assert_eq!(emulator.save_state(), history[2]);
+    assert_eq!(rewind.available_frames(), 0);
+
+    // With no history left the emulator is left alone
+    emulator.run_frame(10).unwrap();
+    let state = emulator.save_state();
+    assert_eq!(rewind.rewind(&mut emulator, 1), Ok(0));
+    assert_eq!(emulator.save_state(), state);
+}
+
+struct Counter;
+
+impl RandomSource for Counter {
+    fn next_byte(state: &mut u64) -> u8 {
+        *state += 1;
+        *state as u8
+    }
+}
+
+#[test]
+fn failed_rewind_keeps_history() {
+    let mut emulator = Chip8Emulator::new(&[0xC0, 0xFF, 0x12, 0x00]);
+    emulator.set_rng(Rng::custom::<Counter>(0));
+    let mut rewind = RewindBuffer::new(4, 1);
+    for _ in 0..3 {
+        emulator.run_frame(2).unwrap();
+        rewind.record(&emulator);
+    }
+
+    // A custom generator's snapshot can't be restored once the emulator has another one
+    emulator.set_rng(Rng::seeded(1));
+    assert_eq!(rewind.rewind(&mut emulator, 1), Err(Chip8Error::InvalidSaveState));
+    assert_eq!(rewind.available_frames(), 2);
+
+    emulator.set_rng(Rng::custom::<Counter>(0));
+    assert_eq!(rewind.rewind(&mut emulator, 2), Ok(2));
+    assert_eq!(emulator.registers().v[0], 1);
+}
@@ -0,0 +1,34 @@
+use chip_8::{Chip8Emulator, Chip8Error, Quirks};
+
+#[test]
+fn round_trip() {
+    let test = std::fs::read("./roms/BC_test.ch8").unwrap();
+    let mut emulator = Chip8Emulator::with_quirks(test.as_slice(), Quirks::SUPER_CHIP);
+    for _ in 0..100 {
+        emulator.tick().unwrap();
+    }
+    let state = emulator.save_state();
+
+    let mut restored = Chip8Emulator::default();
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.quirks(), Quirks::SUPER_CHIP);
+    assert_eq!(restored.save_state(), state);
+
+    for _ in 0..100 {
+        emulator.tick().unwrap();
+        restored.tick().unwrap();
+    }
+    assert_eq!(restored.framebuffer(), emulator.framebuffer());
+}
+
+#[test]
+fn rejects_bad_states() {
+    let mut emulator = Chip8Emulator::default();
+    let mut state = emulator.save_state();
+
+    assert_eq!(emulator.load_state(&state[..state.len() - 1]), Err(Chip8Error::InvalidSaveState));
+    assert_eq!(emulator.load_state(b"not a save state"), Err(Chip8Error::InvalidSaveState));
+
+    state[4] = 0xFF;
+    assert_eq!(emulator.load_state(&state), Err(Chip8Error::UnsupportedSaveStateVersion(0xFF)));
+}
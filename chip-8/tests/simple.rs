@@ -12,7 +12,19 @@ fn test_run() {
     let test = std::fs::read("./roms/BC_test.ch8").unwrap();
     let mut emulator = Chip8Emulator::new(test.as_slice());
     for _ in 0..255 {
-        emulator.tick();
+        emulator.tick().unwrap();
     }
     println!("{emulator}");
 }
+
+#[test]
+fn sound_timer_drives_buzzer() {
+    // v0 := 2; buzzer := v0; loop: jump loop
+    let mut emulator = Chip8Emulator::new(&[0x60, 0x02, 0xF0, 0x18, 0x12, 0x04]);
+    assert!(!emulator.is_sound_active());
+
+    emulator.run_frame(2).unwrap();
+    assert!(emulator.is_sound_active());
+    emulator.run_frame(1).unwrap();
+    assert!(!emulator.is_sound_active());
+}
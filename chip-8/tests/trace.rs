@@ -0,0 +1,113 @@
+use chip_8::{export_text, read_trace, Chip8Emulator, Chip8Error, Quirks, Register, TraceFilter, TraceFormat, Tracer};
+
+// 0x200: v0 := 0
+// 0x202: v0 += 1
+// 0x204: i := 0x300
+// 0x206: save v0
+// 0x208: sprite v0 v0 1
+// 0x20A: jump 0x202
+const COUNTER: [u8; 12] = [0x60, 0x00, 0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0xD0, 0x01, 0x12, 0x02];
+
+#[test]
+fn text_trace() {
+    let mut emulator = Chip8Emulator::new(&COUNTER);
+    let mut tracer = Tracer::new(Vec::new(), TraceFormat::Text).unwrap();
+    for _ in 0..3 {
+        tracer.tick(&mut emulator).unwrap();
+    }
+    assert_eq!(emulator.cycles(), 3);
+
+    let text = String::from_utf8(tracer.into_inner()).unwrap();
+    assert_eq!(text.lines().collect::<Vec<_>>(), [
+        "       1 0200 6000 LD V0, 0x00",
+        "       2 0202 7001 ADD V0, 0x01         V0=01",
+        "       3 0204 A300 LD I, 0x300          I=0300",
+    ]);
+}
+
+#[test]
+fn binary_trace_round_trip() {
+    let mut text = Chip8Emulator::new(&COUNTER);
+    let mut binary = text;
+    let mut text_tracer = Tracer::new(Vec::new(), TraceFormat::Text).unwrap();
+    let mut binary_tracer = Tracer::new(Vec::new(), TraceFormat::Binary).unwrap();
+    for _ in 0..4 {
+        text_tracer.run_frame(&mut text, 10).unwrap();
+        binary_tracer.run_frame(&mut binary, 10).unwrap();
+    }
+
+    let trace = binary_tracer.into_inner();
+    let records = read_trace(&trace).unwrap();
+    assert_eq!(records.len(), 40);
+    assert_eq!(records[1].changes, [(Register::V(0), 1)]);
+
+    let mut exported = Vec::new();
+    export_text(&trace, &mut exported).unwrap();
+    assert_eq!(exported, text_tracer.into_inner());
+
+    assert_eq!(read_trace(&trace[..trace.len() - 1]), Err(Chip8Error::InvalidTrace));
+    assert_eq!(read_trace(b"not a trace"), Err(Chip8Error::InvalidTrace));
+}
+
+#[test]
+fn filters() {
+    let mut emulator = Chip8Emulator::new(&COUNTER);
+    let filter = TraceFilter::new().classes(&[0x7, 0xD]).addresses(0x200..=0x207);
+    let mut tracer = Tracer::new(Vec::new(), TraceFormat::Binary).unwrap().with_filter(filter);
+    tracer.run_frame(&mut emulator, 25).unwrap();
+
+    let records = read_trace(&tracer.into_inner()).unwrap();
+    assert_eq!(records.iter().map(|r| (r.cycle, r.pc)).collect::<Vec<_>>(), [(2, 0x202), (7, 0x202), (12, 0x202), (17, 0x202), (22, 0x202)]);
+}
+
+#[test]
+fn exit_is_traced() {
+    // v0 := 1; exit
+    let mut emulator = Chip8Emulator::new(&[0x60, 0x01, 0x00, 0xFD]);
+    let mut tracer = Tracer::new(Vec::new(), TraceFormat::Text).unwrap();
+    tracer.run_frame(&mut emulator, 10).unwrap();
+    tracer.run_frame(&mut emulator, 10).unwrap();
+    assert!(emulator.is_halted());
+
+    let text = String::from_utf8(tracer.into_inner()).unwrap();
+    assert_eq!(text.lines().collect::<Vec<_>>(), [
+        "       1 0200 6001 LD V0, 0x01          V0=01",
+        "       2 0202 00FD EXIT",
+    ]);
+}
+
+#[test]
+fn faults_are_traced() {
+    // v0 := 1; return with nothing to return to
+    let mut emulator = Chip8Emulator::new(&[0x60, 0x01, 0x00, 0xEE]);
+    let mut text = Tracer::new(Vec::new(), TraceFormat::Text).unwrap();
+    let mut binary = Tracer::new(Vec::new(), TraceFormat::Binary).unwrap();
+    let mut copy = emulator;
+    assert_eq!(text.run_frame(&mut emulator, 10), Err(Chip8Error::StackUnderflow { pc: 0x202 }));
+    assert_eq!(binary.run_frame(&mut copy, 10), Err(Chip8Error::StackUnderflow { pc: 0x202 }));
+
+    let text = String::from_utf8(text.into_inner()).unwrap();
+    assert_eq!(text.lines().collect::<Vec<_>>(), [
+        "       1 0200 6001 LD V0, 0x01          V0=01",
+        "       2 0202 00EE RET                  FAULT",
+    ]);
+    let records = read_trace(&binary.into_inner()).unwrap();
+    assert_eq!(records.iter().map(|r| (r.pc, r.fault)).collect::<Vec<_>>(), [(0x200, false), (0x202, true)]);
+}
+
+#[test]
+fn long_index_load_is_traced_with_its_address() {
+    // i := long 0x1234
+    let mut emulator = Chip8Emulator::with_quirks(&[0xF0, 0x00, 0x12, 0x34], Quirks::XO_CHIP);
+    let mut copy = emulator;
+    let mut text = Tracer::new(Vec::new(), TraceFormat::Text).unwrap();
+    let mut binary = Tracer::new(Vec::new(), TraceFormat::Binary).unwrap();
+    text.tick(&mut emulator).unwrap();
+    binary.tick(&mut copy).unwrap();
+
+    let text = String::from_utf8(text.into_inner()).unwrap();
+    assert_eq!(text.lines().collect::<Vec<_>>(), ["       1 0200 F000 LD I, LONG 0x1234    I=1234"]);
+    let records = read_trace(&binary.into_inner()).unwrap();
+    assert_eq!(records[0].operand, Some(0x1234));
+    assert_eq!(records[0].changes, [(Register::I, 0x1234)]);
+}
@@ -0,0 +1,55 @@
+use chip_8::{Chip8Emulator, Chip8Error, Instruction, Quirks, StepOutcome};
+
+#[test]
+fn large_roms_fit_in_extended_memory() {
+    let rom = vec![0; 0x8000];
+    assert!(Chip8Emulator::try_new(&rom).is_err());
+
+    let mut emulator = Chip8Emulator::default();
+    emulator.set_quirks(Quirks::XO_CHIP);
+    assert_eq!(emulator.load_rom(&rom), Ok(()));
+}
+
+#[test]
+fn long_index_load() {
+    // i := long 0xFFFF; load v0 - v1
+    let rom = [0xF0, 0x00, 0xFF, 0xFF, 0xF1, 0x65];
+    let mut emulator = Chip8Emulator::with_quirks(&rom, Quirks::XO_CHIP);
+    assert_eq!(emulator.tick(), Ok(StepOutcome::Executed));
+    assert_eq!(emulator.registers().i, 0xFFFF);
+    assert_eq!(emulator.registers().pc, 0x204);
+    assert_eq!(emulator.tick(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x10000 }));
+}
+
+#[test]
+fn long_index_load_carries_its_address() {
+    let instruction = Instruction::decode(0xF000, 0xABCD).unwrap();
+    assert_eq!(instruction, Instruction::SetILong { addr: 0xABCD });
+    assert_eq!(instruction.encode(), (0xF000, Some(0xABCD)));
+    assert_eq!(instruction.size(), 4);
+
+    let mut emulator = Chip8Emulator::with_quirks(&[], Quirks::XO_CHIP);
+    assert_eq!(emulator.execute(instruction), Ok(StepOutcome::Executed));
+    assert_eq!(emulator.registers().i, 0xABCD);
+}
+
+#[test]
+fn skips_over_long_index_load() {
+    // if v0 != 0 then i := long 0x0000; clear; <unknown>
+    let rom = [0x30, 0x00, 0xF0, 0x00, 0x00, 0x00, 0x00, 0xE0, 0xFF, 0xFF];
+    let mut emulator = Chip8Emulator::with_quirks(&rom, Quirks::XO_CHIP);
+    emulator.tick().unwrap();
+    emulator.tick().unwrap();
+    assert_eq!(emulator.tick(), Err(Chip8Error::UnknownOpcode { pc: 0x208, opcode: 0xFFFF }));
+}
+
+#[test]
+fn second_plane_draws_its_own_color() {
+    // plane 2; i := 0x20A; sprite v0 v0 1; <sprite 0x80>
+    let rom = [0xF2, 0x01, 0xA2, 0x08, 0xD0, 0x01, 0x00, 0x00, 0x80, 0x00];
+    let mut emulator = Chip8Emulator::with_quirks(&rom, Quirks::XO_CHIP);
+    for _ in 0..3 {
+        emulator.tick().unwrap();
+    }
+    assert_eq!(emulator.framebuffer().pixel(0, 0), 0b10);
+}
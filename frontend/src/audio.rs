@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+use bevy::audio::{AddAudioSource, AudioSinkPlayback, Decodable, Source, Volume};
+use bevy::prelude::*;
+
+use crate::ch8_plugin::{Emulator, EmulatorState};
+
+const SAMPLE_RATE: u32 = 44_100;
+
+/// Sent whenever the buzzer starts or stops, i.e. the sound timer becomes nonzero or runs out.
+#[derive(Message)]
+pub struct BuzzerMessage(pub bool);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl Waveform {
+    pub const ALL: [Self; 4] = [Self::Square, Self::Triangle, Self::Sawtooth, Self::Sine];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Square => "Square",
+            Self::Triangle => "Triangle",
+            Self::Sawtooth => "Sawtooth",
+            Self::Sine => "Sine",
+        }
+    }
+
+    /// The sample at `phase`, a fraction of one period.
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Self::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Self::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Self::Sawtooth => 2.0 * phase - 1.0,
+            Self::Sine => (phase * std::f32::consts::TAU).sin(),
+        }
+    }
+}
+
+/// How the buzzer sounds, edited from the Audio menu.
+#[derive(Resource)]
+pub struct AudioSettings {
+    pub frequency: f32,
+    /// Linear volume between 0 and 1.
+    pub volume: f32,
+    pub waveform: Waveform,
+    pub muted: bool,
+}
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self { frequency: 440.0, volume: 0.25, waveform: Waveform::Square, muted: false }
+    }
+}
+
+/// An endless tone, played in a loop and paused while the buzzer is off.
+#[derive(Asset, TypePath)]
+struct Tone {
+    frequency: f32,
+    waveform: Waveform,
+}
+
+struct ToneDecoder {
+    waveform: Waveform,
+    phase: f32,
+    step: f32,
+}
+
+impl Iterator for ToneDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.waveform.sample(self.phase);
+        self.phase = (self.phase + self.step).fract();
+        Some(sample)
+    }
+}
+
+impl Source for ToneDecoder {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { 1 }
+    fn sample_rate(&self) -> u32 { SAMPLE_RATE }
+    fn total_duration(&self) -> Option<Duration> { None }
+}
+
+impl Decodable for Tone {
+    type DecoderItem = f32;
+    type Decoder = ToneDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        ToneDecoder { waveform: self.waveform, phase: 0.0, step: self.frequency / SAMPLE_RATE as f32 }
+    }
+}
+
+/// The entity playing the tone, respawned when its frequency or waveform changes.
+#[derive(Component)]
+struct Buzzer {
+    frequency: f32,
+    waveform: Waveform,
+}
+
+pub fn audio_plugin(app: &mut App) {
+    app
+        .add_audio_source::<Tone>()
+        .add_message::<BuzzerMessage>()
+        .init_resource::<AudioSettings>()
+        .add_systems(Update, (watch_buzzer, spawn_buzzer, play_buzzer).chain())
+        ;
+}
+
+/// Sends a [`BuzzerMessage`] when the buzzer changes. A paused emulator is silent.
+fn watch_buzzer(
+    emulator: Res<Emulator>,
+    state: Res<EmulatorState>,
+    mut active: Local<bool>,
+    mut buzzer_message: MessageWriter<BuzzerMessage>,
+) {
+    let running = matches!(*state, EmulatorState::Run | EmulatorState::RunToDepth(_));
+    let now = running && emulator.0.is_sound_active();
+    if now != *active {
+        *active = now;
+        buzzer_message.write(BuzzerMessage(now));
+    }
+}
+
+fn spawn_buzzer(
+    mut commands: Commands,
+    mut tones: ResMut<Assets<Tone>>,
+    settings: Res<AudioSettings>,
+    buzzer: Query<(Entity, &Buzzer)>,
+) {
+    if let Ok((entity, buzzer)) = buzzer.single() {
+        if buzzer.frequency == settings.frequency && buzzer.waveform == settings.waveform { return }
+        commands.entity(entity).despawn();
+    }
+
+    let tone = tones.add(Tone { frequency: settings.frequency, waveform: settings.waveform });
+    commands.spawn((
+        AudioPlayer(tone),
+        PlaybackSettings::LOOP.paused(),
+        Buzzer { frequency: settings.frequency, waveform: settings.waveform },
+    ));
+}
+
+/// Plays the tone while the buzzer is on and sound isn't muted.
+fn play_buzzer(
+    mut buzzer_message: MessageReader<BuzzerMessage>,
+    mut active: Local<bool>,
+    settings: Res<AudioSettings>,
+    mut sink: Query<&mut AudioSink, With<Buzzer>>,
+) {
+    if let Some(BuzzerMessage(now)) = buzzer_message.read().last() {
+        *active = *now;
+    }
+
+    // The sink only appears once the audio backend has picked up a freshly spawned buzzer.
+    let Ok(mut sink) = sink.single_mut() else { return };
+    sink.set_volume(Volume::Linear(settings.volume));
+    match *active && !settings.muted {
+        true if sink.is_paused() => sink.play(),
+        false if !sink.is_paused() => sink.pause(),
+        _ => {}
+    }
+}
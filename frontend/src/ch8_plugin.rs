@@ -2,45 +2,178 @@ use std::ops::DerefMut;
 
 use bevy::{prelude::*, window::PrimaryWindow};
 use bevy_ecs_tilemap::prelude::*;
-use chip_8::Chip8Emulator;
+use chip_8::{compile_octo, Chip8Emulator, Debugger, Movie, OctoTarget, Quirks, RewindBuffer, Rng, StopReason, SymbolMap};
 
 #[derive(Message)]
 pub struct LoadRomMessage(pub std::path::PathBuf);
 
+/// Number of quick-save slots offered in the Emulator menu.
+pub const SAVE_SLOTS: u8 = 4;
+
+#[derive(Message)]
+pub struct SaveStateMessage(pub u8);
+
+#[derive(Message)]
+pub struct LoadStateMessage(pub u8);
+
+#[derive(Message)]
+pub struct ResetMessage;
+
+#[derive(Message)]
+pub enum MovieMessage {
+    /// Restarts the loaded program and records a movie to be written to the path on Stop.
+    Record(std::path::PathBuf),
+    /// Restarts the loaded program and plays the movie at the path.
+    Play(std::path::PathBuf),
+    Stop,
+}
+
+/// The movie being recorded or played back. While one is active the movie runs each frame at
+/// its own instructions per frame, ignoring the clock speed and breakpoints, and rewinding,
+/// stepping and memory edits are off, since any of them would break the recording.
+#[derive(Resource, Default)]
+pub enum MovieState {
+    #[default]
+    Idle,
+    Recording { movie: Movie, path: std::path::PathBuf },
+    Playing { movie: Movie, frame: usize },
+}
+
+impl MovieState {
+    pub fn is_playing(&self) -> bool {
+        matches!(self, Self::Playing { .. })
+    }
+
+    pub fn is_active(&self) -> bool {
+        !matches!(self, Self::Idle)
+    }
+
+    /// Ends the movie, writing out a recording.
+    fn stop(&mut self, emulator: &Chip8Emulator) {
+        if let Self::Recording { movie, path } = self {
+            movie.finish(emulator);
+            match std::fs::write(&*path, movie.to_bytes()) {
+                Ok(()) => println!("Recorded {} frames to {}", movie.frames.len(), path.display()),
+                Err(e) => eprintln!("Could not write {}: {e}", path.display()),
+            }
+        }
+        *self = Self::Idle;
+    }
+}
+
 #[derive(Resource)]
-struct Emulator(Chip8Emulator);
+pub struct Emulator(pub Chip8Emulator);
 
-#[derive(Resource, Eq, PartialEq)]
-enum EmulatorState {
+#[derive(Resource, Copy, Clone, Eq, PartialEq)]
+pub enum EmulatorState {
     Stop,
     Step,
     Run,
+    /// Runs until the stack is at most this deep, for Step Over and Step Out.
+    RunToDepth(u8),
 }
 impl Default for EmulatorState {
     fn default() -> Self { Self::Stop }
 }
 
+/// Recent emulator history, stepped back through while [`REWIND_KEY`] is held.
+#[derive(Resource)]
+struct Rewind(RewindBuffer);
+impl Default for Rewind {
+    fn default() -> Self { Self(RewindBuffer::new(600, 1)) }
+}
+
+const REWIND_KEY: KeyCode = KeyCode::Backspace;
+const PAUSE_KEY: KeyCode = KeyCode::F5;
+const STEP_KEY: KeyCode = KeyCode::F10;
+
+/// The program last loaded, kept for Reset, along with its symbols if it came with any.
+#[derive(Resource, Default)]
+pub struct LoadedRom {
+    pub path: Option<std::path::PathBuf>,
+    pub program: Option<Vec<u8>>,
+    pub symbols: SymbolMap,
+}
+
+impl LoadedRom {
+    /// The file name without its extension, which per-ROM settings are stored under.
+    pub fn name(&self) -> Option<&str> {
+        self.path.as_deref()?.file_stem()?.to_str()
+    }
+}
+
+/// Breakpoints checked while running; hitting one pauses the emulator.
+#[derive(Resource, Default)]
+pub struct Breakpoints(pub Debugger);
+
+/// Instructions executed per 60 Hz frame. Timers always tick at 60 Hz regardless.
+#[derive(Resource)]
+pub struct ClockSpeed(pub usize);
+impl Default for ClockSpeed {
+    fn default() -> Self { Self(11) }
+}
+
 pub fn chip8_emulator_plugin(app: &mut App) {
-    let emu_resource = Emulator(Chip8Emulator::new(&[]));
+    // Seeded from the clock so games play out differently between sessions.
+    let mut emu_resource = Emulator(Chip8Emulator::default());
+    let seed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |t| t.as_nanos() as u64);
+    emu_resource.0.set_rng(Rng::seeded(seed));
 
     app
         .add_plugins(TilemapPlugin)
         .add_systems(Startup, setup)
         .add_message::<LoadRomMessage>()
+        .add_message::<SaveStateMessage>()
+        .add_message::<LoadStateMessage>()
+        .add_message::<ResetMessage>()
+        .add_message::<MovieMessage>()
         .insert_resource(Time::<Fixed>::from_hz(60.0))
         .init_resource::<EmulatorState>()
+        .init_resource::<ClockSpeed>()
+        .init_resource::<Rewind>()
+        .init_resource::<Breakpoints>()
+        .init_resource::<LoadedRom>()
+        .init_resource::<MovieState>()
         .insert_resource(emu_resource)
-        .add_systems(FixedUpdate, update_emulator.run_if(resource_equals(EmulatorState::Run)))
-        .add_systems(Update, reload_emulator)
+        .add_systems(FixedUpdate, (
+            update_emulator.run_if(|state: Res<EmulatorState>| matches!(*state, EmulatorState::Run | EmulatorState::RunToDepth(_))),
+            step_emulator.run_if(resource_equals(EmulatorState::Step)),
+        ))
+        .add_systems(Update, (movie_control, reload_emulator, reset_emulator, quick_save_load, debug_keys, resize_screen, draw_screen).chain())
         ;
 }
 
+/// Tile colors indexed by the plane bits of a pixel: off, first plane, second plane, both.
+const PALETTE: [[u8; 4]; 4] = [
+    [0, 0, 0, 255],
+    [255, 255, 255, 255],
+    [255, 170, 0, 255],
+    [170, 85, 0, 255],
+];
+
+/// The tilemap showing the emulator display, sized to the resolution it was spawned for.
+#[derive(Component)]
+struct Screen {
+    width: u32,
+    height: u32,
+}
+
 fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     window: Single<&Window, With<PrimaryWindow>>,
 ) {
-    let map_size = TilemapSize { x: 64, y: 32 };
+    spawn_screen(&mut commands, &asset_server, &window, 64, 32);
+}
+
+fn spawn_screen(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    window: &Window,
+    width: u32,
+    height: u32,
+) {
+    let map_size = TilemapSize { x: width, y: height };
 
     let tilemap_entity = commands.spawn_empty().id();
     let mut tilemap_storage = TileStorage::empty(map_size);
@@ -49,63 +182,177 @@ fn setup(
         TileTextureIndex(0), 
         map_size, 
         TilemapId(tilemap_entity), 
-        &mut commands, 
+        commands, 
         &mut tilemap_storage
     );
 
     let single_tile_size = std::cmp::min(
-        (window.width() as u32 - 10) / 64,
-        (window.height() as u32 - 10) / 32,
+        (window.width() as u32 - 10) / width,
+        (window.height() as u32 - 10) / height,
     ) as f32;
     let tile_size = TilemapTileSize::new(single_tile_size, single_tile_size);
     let grid_size = tile_size.into();
     let map_type = TilemapType::default();
 
-    let white_image = Image::new_fill(
-        bevy::render::render_resource::Extent3d { width: single_tile_size as u32, height: single_tile_size as u32, depth_or_array_layers: 1 },
-        bevy::render::render_resource::TextureDimension::D2,
-        &[255, 255, 255, 255],
-        bevy::render::render_resource::TextureFormat::Rgba8UnormSrgb,
-        bevy::asset::RenderAssetUsages::RENDER_WORLD,
-    );
-    let white_image_handle = asset_server.add(white_image);
-
-    let black_image = Image::new_fill(
-        bevy::render::render_resource::Extent3d { width: single_tile_size as u32, height: single_tile_size as u32, depth_or_array_layers: 1 },
-        bevy::render::render_resource::TextureDimension::D2,
-        &[0, 0, 0, 255],
-        bevy::render::render_resource::TextureFormat::Rgba8UnormSrgb,
-        bevy::asset::RenderAssetUsages::RENDER_WORLD,
-    );
-    let black_image_handle = asset_server.add(black_image);
+    let texture = TilemapTexture::Vector(PALETTE.iter()
+        .map(|color| {
+            let image = Image::new_fill(
+                bevy::render::render_resource::Extent3d { width: single_tile_size as u32, height: single_tile_size as u32, depth_or_array_layers: 1 },
+                bevy::render::render_resource::TextureDimension::D2,
+                color,
+                bevy::render::render_resource::TextureFormat::Rgba8UnormSrgb,
+                bevy::asset::RenderAssetUsages::RENDER_WORLD,
+            );
+            asset_server.add(image)
+        })
+        .collect());
 
-    let texture = TilemapTexture::Vector(vec![black_image_handle, white_image_handle]);
+    commands.entity(tilemap_entity).insert((
+        TilemapBundle {
+            grid_size, map_type, texture, tile_size,
+            size: map_size,
+            storage: tilemap_storage,
+            anchor: TilemapAnchor::Center,
+            ..default()
+        },
+        Screen { width, height },
+    ));
+}
 
-    commands.entity(tilemap_entity).insert(TilemapBundle {
-        grid_size, map_type, texture, tile_size,
-        size: map_size,
-        storage: tilemap_storage,
-        anchor: TilemapAnchor::Center,
-        ..default()
-    });
+fn resize_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    emulator: Res<Emulator>,
+    screen: Single<(Entity, &Screen, &TileStorage)>,
+) {
+    let (entity, screen, storage) = screen.into_inner();
+    let width = emulator.0.display_width() as u32;
+    let height = emulator.0.display_height() as u32;
+    if screen.width == width && screen.height == height { return }
+
+    for tile in storage.iter().flatten() {
+        commands.entity(*tile).try_despawn();
+    }
+    commands.entity(entity).despawn();
+    spawn_screen(&mut commands, &asset_server, &window, width, height);
 }
 
 fn update_emulator(
     mut emulator: ResMut<Emulator>,
     mut state: ResMut<EmulatorState>,
-    mut tile_query: Query<(&TilePos, &mut TileTextureIndex)>,
+    speed: Res<ClockSpeed>,
+    breakpoints: Res<Breakpoints>,
+    mut rewind: ResMut<Rewind>,
+    mut movie: ResMut<MovieState>,
+    keys: Res<ButtonInput<KeyCode>>,
 ) {
-    let tick_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| emulator.0.tick()));
-    if let Err(e) = tick_result {
-        eprintln!("{e:?}");
-        println!("{}", emulator.0);
-        *state.deref_mut() = EmulatorState::Stop;
+    if movie.is_active() {
+        run_movie_frame(movie.deref_mut(), &mut emulator.0, state.deref_mut());
+        return
+    }
+    if keys.pressed(REWIND_KEY) {
+        if let Err(e) = rewind.0.rewind(&mut emulator.0, 1) {
+            eprintln!("could not rewind: {e}");
+            rewind.0.clear();
+        }
         return
     }
-    for (pos, mut texture) in tile_query.iter_mut() {
-        let tile_pos = ((31 - pos.y) * 64 + pos.x) as usize;
-        let d_pixel = emulator.0.display_ram[tile_pos];
-        texture.0 = (d_pixel & 0x80) as u32;
+
+    let depth = match *state {
+        EmulatorState::RunToDepth(depth) => Some(depth),
+        _ => None,
+    };
+    match emulator.0.run_until(&breakpoints.0, speed.0, |r| depth.is_some_and(|depth| r.sp <= depth)) {
+        Err(e) => {
+            eprintln!("Emulator fault: {e}");
+            println!("{}", emulator.0);
+            *state.deref_mut() = EmulatorState::Stop;
+            return
+        }
+        Ok(StopReason::Breakpoint(id)) => {
+            println!("Breakpoint {:?} hit at 0x{:03X}", breakpoints.0.get(id), emulator.0.registers().pc);
+            *state.deref_mut() = EmulatorState::Stop;
+        }
+        Ok(StopReason::Finished) => *state.deref_mut() = EmulatorState::Stop,
+        Ok(_) => {}
+    }
+    emulator.0.tick_timers();
+    rewind.0.record(&emulator.0);
+}
+
+/// Runs the next frame of the active movie, which decides how many instructions that is.
+fn run_movie_frame(movie: &mut MovieState, emulator: &mut Chip8Emulator, state: &mut EmulatorState) {
+    let result = match movie {
+        MovieState::Idle => return,
+        MovieState::Recording { movie, .. } => movie.record_frame(emulator),
+        MovieState::Playing { movie: playing, frame } => match playing.play_frame(emulator, *frame) {
+            Ok(true) => {
+                *frame += 1;
+                Ok(())
+            }
+            Ok(false) => {
+                match playing.verify(emulator) {
+                    Ok(()) => println!("Movie finished after {frame} frames"),
+                    Err(e) => eprintln!("Movie finished after {frame} frames: {e}"),
+                }
+                *movie = MovieState::Idle;
+                *state = EmulatorState::Stop;
+                Ok(())
+            }
+            Err(e) => Err(e),
+        },
+    };
+
+    if let Err(e) = result {
+        eprintln!("Emulator fault: {e}");
+        println!("{emulator}");
+        *state = EmulatorState::Stop;
+    }
+}
+
+/// Runs a single instruction, then pauses again.
+fn step_emulator(mut emulator: ResMut<Emulator>, mut state: ResMut<EmulatorState>) {
+    if let Err(e) = emulator.0.tick() {
+        eprintln!("Emulator fault: {e}");
+    }
+    *state.deref_mut() = EmulatorState::Stop;
+}
+
+fn debug_keys(keys: Res<ButtonInput<KeyCode>>, movie: Res<MovieState>, mut state: ResMut<EmulatorState>) {
+    if keys.just_pressed(PAUSE_KEY) {
+        let next = if *state == EmulatorState::Run { EmulatorState::Stop } else { EmulatorState::Run };
+        *state.deref_mut() = next;
+    }
+    if keys.just_pressed(STEP_KEY) && !movie.is_active() {
+        *state.deref_mut() = EmulatorState::Step;
+    }
+}
+
+/// Updates the tiles in the area the emulator has drawn to since the last frame.
+fn draw_screen(
+    mut emulator: ResMut<Emulator>,
+    screen: Single<(&Screen, &TileStorage)>,
+    mut tile_query: Query<&mut TileTextureIndex>,
+) {
+    let (screen, storage) = screen.into_inner();
+    let width = emulator.0.display_width() as u32;
+    let height = emulator.0.display_height() as u32;
+    // The screen is respawned at the new size on the next Update after a resolution switch;
+    // keep the dirty area until then.
+    if screen.width != width || screen.height != height { return }
+    let Some(dirty) = emulator.0.take_dirty() else { return };
+
+    let framebuffer = emulator.0.framebuffer();
+    for y in dirty.y..dirty.y + dirty.height {
+        let row = framebuffer.row(y);
+        for x in dirty.x..dirty.x + dirty.width {
+            let pos = TilePos { x: x as u32, y: height - 1 - y as u32 };
+            let Some(tile) = storage.get(&pos) else { continue };
+            if let Ok(mut texture) = tile_query.get_mut(tile) {
+                texture.0 = row.pixel(x) as u32;
+            }
+        }
     }
 }
 
@@ -113,19 +360,150 @@ fn reload_emulator(
     mut rom_message: MessageReader<LoadRomMessage>,
     mut emulator: ResMut<Emulator>,
     mut state: ResMut<EmulatorState>,
+    mut rewind: ResMut<Rewind>,
+    mut loaded: ResMut<LoadedRom>,
+    mut movie: ResMut<MovieState>,
 ) {
     for ev in rom_message.read() {
-        let path = ev.0.clone();
-        let path_result = std::panic::catch_unwind(|| {
-            std::fs::read(path).unwrap()
+        movie.stop(&emulator.0);
+        // Plain ROMs say nothing about their platform, so they keep the profile already in use
+        let current_quirks = emulator.0.quirks();
+        let read_result = match ev.0.extension().and_then(|ext| ext.to_str()) {
+            Some("8o") => compile_octo_file(&ev.0),
+            _ => std::fs::read(&ev.0)
+                .map(|program| (program, read_symbols(&ev.0), current_quirks))
+                .map_err(|e| e.to_string()),
+        };
+
+        let load_result = read_result.and_then(|(program, symbols, quirks)| {
+            emulator.0.set_quirks(quirks);
+            emulator.0.load_rom(&program).map_err(|e| e.to_string())?;
+            *loaded.deref_mut() = LoadedRom { path: Some(ev.0.clone()), program: Some(program), symbols };
+            Ok(())
         });
 
-        let contents = match path_result {
-            Err(e) => { eprintln!("Could not read selected file: {e:?}"); return },
+        if let Err(e) = load_result {
+            eprintln!("Could not load {}: {e}", ev.0.display());
+            return
+        }
+
+        rewind.0.clear();
+        *state.deref_mut() = EmulatorState::Run;
+    }
+}
+
+/// Compiles Octo source for XO-CHIP, the superset Octo targets by default, to run with
+/// matching quirks.
+fn compile_octo_file(path: &std::path::Path) -> Result<(Vec<u8>, SymbolMap, Quirks), String> {
+    let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let assembly = compile_octo(&source, OctoTarget::XoChip).map_err(|e| e.to_string())?;
+    Ok((assembly.bytes, assembly.symbols, OctoTarget::XoChip.quirks()))
+}
+
+/// The symbol map `chip8-asm` writes next to a ROM, if there is one.
+fn read_symbols(rom: &std::path::Path) -> SymbolMap {
+    std::fs::read_to_string(rom.with_extension("sym"))
+        .ok()
+        .and_then(|symbols| symbols.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Restarts the loaded program, keeping the current quirks and run state.
+fn reset_emulator(
+    mut reset_message: MessageReader<ResetMessage>,
+    mut emulator: ResMut<Emulator>,
+    mut rewind: ResMut<Rewind>,
+    loaded: Res<LoadedRom>,
+    mut movie: ResMut<MovieState>,
+) {
+    for _ in reset_message.read() {
+        let Some(program) = &loaded.program else { continue };
+        movie.stop(&emulator.0);
+        if let Err(e) = emulator.0.load_rom(program) {
+            eprintln!("Could not reset: {e}");
+        }
+        rewind.0.clear();
+    }
+}
+
+fn save_slot_path(slot: u8) -> std::path::PathBuf {
+    std::path::Path::new("saves").join(format!("slot{slot}.c8s"))
+}
+
+fn quick_save_load(
+    mut save_message: MessageReader<SaveStateMessage>,
+    mut load_message: MessageReader<LoadStateMessage>,
+    mut emulator: ResMut<Emulator>,
+    mut rewind: ResMut<Rewind>,
+    mut movie: ResMut<MovieState>,
+) {
+    for ev in save_message.read() {
+        let path = save_slot_path(ev.0);
+        let save_result = std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| std::fs::write(&path, emulator.0.save_state()));
+
+        if let Err(e) = save_result {
+            eprintln!("Could not write {}: {e}", path.display());
+        }
+    }
+
+    for ev in load_message.read() {
+        let path = save_slot_path(ev.0);
+        let state = match std::fs::read(&path) {
+            Err(e) => { eprintln!("Could not read {}: {e}", path.display()); continue },
             Ok(v) => v,
         };
 
-        emulator.0 = Chip8Emulator::new(contents.as_slice());
-        *state.deref_mut() = EmulatorState::Run;
+        movie.stop(&emulator.0);
+        match emulator.0.load_state(&state) {
+            // History from before the load belongs to another timeline
+            Ok(()) => rewind.0.clear(),
+            Err(e) => eprintln!("Could not restore {}: {e}", path.display()),
+        }
+    }
+}
+
+/// Starts and stops movies. Both recording and playback restart the loaded program, since a
+/// movie always begins at power-on.
+fn movie_control(
+    mut movie_message: MessageReader<MovieMessage>,
+    mut emulator: ResMut<Emulator>,
+    mut state: ResMut<EmulatorState>,
+    mut rewind: ResMut<Rewind>,
+    mut movie: ResMut<MovieState>,
+    loaded: Res<LoadedRom>,
+    speed: Res<ClockSpeed>,
+) {
+    for ev in movie_message.read() {
+        movie.stop(&emulator.0);
+        let path = match ev {
+            MovieMessage::Stop => continue,
+            MovieMessage::Record(path) | MovieMessage::Play(path) => path,
+        };
+        let Some(program) = &loaded.program else {
+            eprintln!("Load a ROM before starting a movie");
+            continue
+        };
+
+        let started = match ev {
+            MovieMessage::Record(_) => Movie::record(&mut emulator.0, program, speed.0)
+                .map(|recording| MovieState::Recording { movie: recording, path: path.clone() })
+                .map_err(|e| e.to_string()),
+            _ => std::fs::read(path).map_err(|e| e.to_string())
+                .and_then(|bytes| Movie::from_bytes(&bytes).map_err(|e| e.to_string()))
+                .and_then(|playing| {
+                    playing.start_playback(&mut emulator.0, program).map_err(|e| e.to_string())?;
+                    Ok(MovieState::Playing { movie: playing, frame: 0 })
+                }),
+        };
+
+        match started {
+            Ok(started) => {
+                *movie.deref_mut() = started;
+                rewind.0.clear();
+                *state.deref_mut() = EmulatorState::Run;
+            }
+            Err(e) => eprintln!("Could not start movie {}: {e}", path.display()),
+        }
     }
 }
@@ -1,16 +1,53 @@
 use bevy::prelude::*;
 use bevy_egui::*;
+use chip_8::{disassemble_rom, Instruction};
+
+use crate::audio::{AudioSettings, Waveform};
+use crate::ch8_plugin::{Emulator, EmulatorState, LoadedRom, MovieState, ResetMessage};
+use crate::input::{key_name, ButtonMap, Keymap, BINDABLE_BUTTONS, BINDABLE_KEYS, KEYPAD_LAYOUT};
 
 pub fn gui_plugin(app: &mut App) {
     app
         .add_plugins(EguiPlugin::default())
-        .add_systems(EguiPrimaryContextPass, ui_menu_bar)
+        .init_resource::<DebuggerWindow>()
+        .init_resource::<KeypadWindow>()
+        .add_systems(EguiPrimaryContextPass, (ui_menu_bar, ui_debugger, ui_keypad).chain())
         ;
 }
 
+/// Instructions shown before the program counter in the disassembly view.
+const DISASSEMBLY_CONTEXT: usize = 6;
+const DISASSEMBLY_LINES: usize = 20;
+const HEX_ROW_BYTES: usize = 16;
+const HEX_ROWS: usize = 16;
+
+#[derive(Resource, Default)]
+struct DebuggerWindow {
+    open: bool,
+    /// First address shown in the hex viewer.
+    hex_address: usize,
+    /// The byte being edited and the text typed for it so far.
+    editing: Option<(usize, String)>,
+}
+
+#[derive(Resource, Default)]
+struct KeypadWindow {
+    open: bool,
+    /// The keypad key waiting for a host key to be pressed.
+    rebinding: Option<u8>,
+}
+
 fn ui_menu_bar(
     mut contexts: EguiContexts,
     mut rom_event: MessageWriter<crate::ch8_plugin::LoadRomMessage>,
+    mut save_event: MessageWriter<crate::ch8_plugin::SaveStateMessage>,
+    mut load_event: MessageWriter<crate::ch8_plugin::LoadStateMessage>,
+    mut movie_event: MessageWriter<crate::ch8_plugin::MovieMessage>,
+    movie: Res<MovieState>,
+    mut speed: ResMut<crate::ch8_plugin::ClockSpeed>,
+    mut debugger: ResMut<DebuggerWindow>,
+    mut keypad: ResMut<KeypadWindow>,
+    mut audio: ResMut<AudioSettings>,
 ) {
     egui::TopBottomPanel::top("menu_bar").show(contexts.ctx_mut().unwrap(), |ui| {
         egui::MenuBar::new().ui(ui, |ui| {
@@ -26,7 +63,298 @@ fn ui_menu_bar(
                         rom_event.write(crate::ch8_plugin::LoadRomMessage(v[0].clone()));
                     }
                 }
+                ui.separator();
+                ui.menu_button("Quick Save", |ui| {
+                    for slot in 1..=crate::ch8_plugin::SAVE_SLOTS {
+                        if ui.button(format!("Slot {slot}")).clicked() {
+                            save_event.write(crate::ch8_plugin::SaveStateMessage(slot));
+                        }
+                    }
+                });
+                ui.menu_button("Quick Load", |ui| {
+                    for slot in 1..=crate::ch8_plugin::SAVE_SLOTS {
+                        if ui.button(format!("Slot {slot}")).clicked() {
+                            load_event.write(crate::ch8_plugin::LoadStateMessage(slot));
+                        }
+                    }
+                });
+                ui.separator();
+                let idle = !movie.is_active();
+                if ui.add_enabled(idle, egui::Button::new("Record Movie")).clicked() {
+                    let res = rfd::FileDialog::new()
+                        .set_directory(std::env::current_dir().unwrap())
+                        .add_filter("Movie", &["c8m"])
+                        .save_file();
+                    if let Some(path) = res {
+                        movie_event.write(crate::ch8_plugin::MovieMessage::Record(path));
+                    }
+                }
+                if ui.add_enabled(idle, egui::Button::new("Play Movie")).clicked() {
+                    let res = rfd::FileDialog::new()
+                        .set_directory(std::env::current_dir().unwrap())
+                        .add_filter("Movie", &["c8m"])
+                        .pick_file();
+                    if let Some(path) = res {
+                        movie_event.write(crate::ch8_plugin::MovieMessage::Play(path));
+                    }
+                }
+                if ui.add_enabled(!idle, egui::Button::new("Stop Movie")).clicked() {
+                    movie_event.write(crate::ch8_plugin::MovieMessage::Stop);
+                }
+                ui.separator();
+                // A movie runs at the speed it was recorded at
+                ui.add_enabled(idle, egui::Slider::new(&mut speed.0, 1..=100).text("Instructions / frame"));
+            });
+            ui.menu_button("View", |ui| {
+                ui.checkbox(&mut debugger.open, "Debugger");
+                ui.checkbox(&mut keypad.open, "Keypad");
+            });
+            ui.menu_button("Audio", |ui| {
+                ui.checkbox(&mut audio.muted, "Mute");
+                ui.separator();
+                ui.add(egui::Slider::new(&mut audio.volume, 0.0..=1.0).text("Volume"));
+                ui.add(egui::Slider::new(&mut audio.frequency, 100.0..=2000.0).logarithmic(true).suffix(" Hz").text("Frequency"));
+                for waveform in Waveform::ALL {
+                    ui.radio_value(&mut audio.waveform, waveform, waveform.name());
+                }
+            });
+        });
+    });
+}
+
+fn ui_debugger(
+    mut contexts: EguiContexts,
+    mut window: ResMut<DebuggerWindow>,
+    mut emulator: ResMut<Emulator>,
+    mut state: ResMut<EmulatorState>,
+    loaded: Res<LoadedRom>,
+    movie: Res<MovieState>,
+    mut reset_event: MessageWriter<ResetMessage>,
+) {
+    if !window.open { return }
+    // Stepping or editing memory during a movie would break it
+    let can_edit = !movie.is_active();
+    let mut open = true;
+
+    egui::Window::new("Debugger").open(&mut open).show(contexts.ctx_mut().unwrap(), |ui| {
+        let registers = emulator.0.registers();
+
+        ui.horizontal(|ui| {
+            if ui.button("Run").clicked() {
+                *state = EmulatorState::Run;
+            }
+            if ui.button("Pause").clicked() {
+                *state = EmulatorState::Stop;
+            }
+            if ui.add_enabled(can_edit, egui::Button::new("Step")).clicked() {
+                *state = EmulatorState::Step;
+            }
+            if ui.add_enabled(can_edit, egui::Button::new("Step Over")).clicked() {
+                let pc = registers.pc as usize;
+                let opcode = emulator.0.memory().get(pc..pc + 2).map(|w| u16::from_be_bytes([w[0], w[1]]));
+                let is_call = matches!(opcode.and_then(|opcode| Instruction::decode(opcode, 0)), Some(Instruction::CallAddr { .. }));
+                *state = if is_call { EmulatorState::RunToDepth(registers.sp) } else { EmulatorState::Step };
+            }
+            if ui.add_enabled(can_edit && registers.sp > 0, egui::Button::new("Step Out")).clicked() {
+                *state = EmulatorState::RunToDepth(registers.sp - 1);
+            }
+            if ui.button("Reset").clicked() {
+                reset_event.write(ResetMessage);
+            }
+        });
+        ui.separator();
+
+        ui.columns(2, |columns| {
+            egui::Grid::new("registers").striped(true).show(&mut columns[0], |ui| {
+                for (row, values) in registers.v.chunks(4).enumerate() {
+                    for (i, value) in values.iter().enumerate() {
+                        ui.monospace(format!("V{:X} {value:02X}", row * 4 + i));
+                    }
+                    ui.end_row();
+                }
+                ui.monospace(format!("I {:04X}", registers.i));
+                ui.monospace(format!("PC {:03X}", registers.pc));
+                ui.monospace(format!("DT {:02X}", registers.delay));
+                ui.monospace(format!("ST {:02X}", registers.sound));
+                ui.end_row();
+            });
+
+            let ui = &mut columns[1];
+            ui.monospace(format!("SP {}", registers.sp));
+            for (depth, address) in emulator.0.stack().iter().enumerate().rev() {
+                let label = loaded.symbols.label_at(*address).map(|l| format!(" ({l})")).unwrap_or_default();
+                ui.monospace(format!("{depth:>2}: {address:03X}{label}"));
+            }
+        });
+        ui.separator();
+
+        ui.collapsing("Disassembly", |ui| {
+            let pc = registers.pc as usize;
+            let start = pc.saturating_sub(DISASSEMBLY_CONTEXT * 2);
+            let memory = emulator.0.memory();
+            let end = (start + DISASSEMBLY_LINES * 2).min(memory.len());
+            for line in disassemble_rom(&memory[start.min(end)..end], start as u16) {
+                if let Some(label) = loaded.symbols.label_at(line.address) {
+                    ui.monospace(format!("{label}:"));
+                }
+                let text = egui::RichText::new(line.to_string()).monospace();
+                if line.address as usize == pc {
+                    ui.label(text.color(egui::Color32::YELLOW));
+                } else {
+                    ui.label(text);
+                }
+            }
+        });
+
+        ui.collapsing("Memory", |ui| {
+            let size = emulator.0.memory().len();
+            ui.horizontal(|ui| {
+                ui.label("Address");
+                ui.add(egui::DragValue::new(&mut window.hex_address)
+                    .hexadecimal(4, false, true)
+                    .range(0..=size.saturating_sub(HEX_ROW_BYTES)));
+                if ui.button("I").clicked() {
+                    window.hex_address = registers.i as usize;
+                }
+                if ui.button("PC").clicked() {
+                    window.hex_address = registers.pc as usize;
+                }
+            });
+            window.hex_address -= window.hex_address % HEX_ROW_BYTES;
+
+            for row in 0..HEX_ROWS {
+                let row_start = window.hex_address + row * HEX_ROW_BYTES;
+                if row_start >= size { break }
+
+                ui.horizontal(|ui| {
+                    ui.monospace(format!("{row_start:04X}"));
+                    for address in row_start..(row_start + HEX_ROW_BYTES).min(size) {
+                        let value = emulator.0.memory()[address];
+                        let selected = matches!(window.editing, Some((editing, _)) if editing == address);
+                        let label = egui::RichText::new(format!("{value:02X}")).monospace();
+                        if ui.selectable_label(selected, label).clicked() && can_edit {
+                            window.editing = Some((address, format!("{value:02X}")));
+                        }
+                    }
+                });
+            }
+
+            let Some((address, mut text)) = window.editing.take() else { return };
+            ui.horizontal(|ui| {
+                ui.monospace(format!("{address:04X} ="));
+                let response = ui.add(egui::TextEdit::singleline(&mut text).desired_width(32.0));
+                let committed = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if committed && can_edit {
+                    match u8::from_str_radix(text.trim(), 16) {
+                        Ok(value) => {
+                            if let Err(e) = emulator.0.write_memory(address, value) {
+                                eprintln!("Could not write memory: {e}");
+                            }
+                        }
+                        Err(e) => eprintln!("Invalid byte {text}: {e}"),
+                    }
+                } else if !ui.button("Cancel").clicked() {
+                    window.editing = Some((address, text));
+                }
             });
         });
     });
+
+    window.open = open;
+}
+
+/// Shows the keypad with the host key bound to each button. Clicking a button rebinds it to
+/// the next key pressed; Escape cancels. Below it, each gamepad button can be pointed at a
+/// keypad key. Bindings are saved for the loaded ROM.
+fn ui_keypad(
+    mut contexts: EguiContexts,
+    mut window: ResMut<KeypadWindow>,
+    mut keymap: ResMut<Keymap>,
+    mut buttons: ResMut<ButtonMap>,
+    emulator: Res<Emulator>,
+    loaded: Res<LoadedRom>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !window.open { return }
+
+    if let Some(chip8) = window.rebinding {
+        if keys.just_pressed(KeyCode::Escape) {
+            window.rebinding = None;
+        } else if let Some((host, _)) = BINDABLE_KEYS.iter().find(|(host, _)| keys.just_pressed(*host)) {
+            keymap.bind(chip8, *host);
+            window.rebinding = None;
+            save_keymap(&keymap, &loaded);
+        }
+    }
+
+    let mut open = true;
+    egui::Window::new("Keypad").open(&mut open).show(contexts.ctx_mut().unwrap(), |ui| {
+        match loaded.name() {
+            Some(name) => ui.label(format!("Bindings for {name}")),
+            None => ui.label("Load a ROM to save bindings"),
+        };
+
+        egui::Grid::new("keypad").show(ui, |ui| {
+            for row in KEYPAD_LAYOUT.chunks(4) {
+                for chip8 in row {
+                    let label = if window.rebinding == Some(*chip8) {
+                        format!("{chip8:X}: ...")
+                    } else {
+                        format!("{chip8:X}: {}", key_name(keymap.0[*chip8 as usize]))
+                    };
+                    let pressed = emulator.0.is_key_pressed(*chip8);
+                    if ui.add(egui::Button::new(egui::RichText::new(label).monospace()).selected(pressed)).clicked() {
+                        window.rebinding = Some(*chip8);
+                    }
+                }
+                ui.end_row();
+            }
+        });
+
+        if ui.button("Restore Defaults").clicked() {
+            *keymap = Keymap::default();
+            window.rebinding = None;
+            save_keymap(&keymap, &loaded);
+        }
+
+        ui.collapsing("Gamepad", |ui| {
+            let mut changed = false;
+            egui::Grid::new("gamepad").show(ui, |ui| {
+                for ((_, name), chip8) in BINDABLE_BUTTONS.iter().zip(buttons.0.iter_mut()) {
+                    ui.label(*name);
+                    let selected = chip8.map_or("-".to_string(), |k| format!("{k:X}"));
+                    egui::ComboBox::from_id_salt(name).selected_text(selected).show_ui(ui, |ui| {
+                        changed |= ui.selectable_value(chip8, None, "-").changed();
+                        for key in 0..16 {
+                            changed |= ui.selectable_value(chip8, Some(key), format!("{key:X}")).changed();
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+
+            if ui.button("Restore Preset").clicked() {
+                *buttons = loaded.name().map(ButtonMap::preset).unwrap_or_default();
+                changed = true;
+            }
+            if changed {
+                save_buttons(&buttons, &loaded);
+            }
+        });
+    });
+    window.open = open;
+}
+
+fn save_keymap(keymap: &Keymap, loaded: &LoadedRom) {
+    let Some(name) = loaded.name() else { return };
+    if let Err(e) = keymap.save(name) {
+        eprintln!("Could not save keymap for {name}: {e}");
+    }
+}
+
+fn save_buttons(buttons: &ButtonMap, loaded: &LoadedRom) {
+    let Some(name) = loaded.name() else { return };
+    if let Err(e) = buttons.save(name) {
+        eprintln!("Could not save gamepad bindings for {name}: {e}");
+    }
 }
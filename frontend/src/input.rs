@@ -0,0 +1,237 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+use crate::ch8_plugin::{Emulator, LoadedRom, MovieState};
+
+/// Host keys that can be bound to the keypad, with the names keymap files use for them.
+pub const BINDABLE_KEYS: &[(KeyCode, &str)] = &[
+    (KeyCode::Digit0, "0"), (KeyCode::Digit1, "1"), (KeyCode::Digit2, "2"), (KeyCode::Digit3, "3"),
+    (KeyCode::Digit4, "4"), (KeyCode::Digit5, "5"), (KeyCode::Digit6, "6"), (KeyCode::Digit7, "7"),
+    (KeyCode::Digit8, "8"), (KeyCode::Digit9, "9"),
+    (KeyCode::KeyA, "A"), (KeyCode::KeyB, "B"), (KeyCode::KeyC, "C"), (KeyCode::KeyD, "D"),
+    (KeyCode::KeyE, "E"), (KeyCode::KeyF, "F"), (KeyCode::KeyG, "G"), (KeyCode::KeyH, "H"),
+    (KeyCode::KeyI, "I"), (KeyCode::KeyJ, "J"), (KeyCode::KeyK, "K"), (KeyCode::KeyL, "L"),
+    (KeyCode::KeyM, "M"), (KeyCode::KeyN, "N"), (KeyCode::KeyO, "O"), (KeyCode::KeyP, "P"),
+    (KeyCode::KeyQ, "Q"), (KeyCode::KeyR, "R"), (KeyCode::KeyS, "S"), (KeyCode::KeyT, "T"),
+    (KeyCode::KeyU, "U"), (KeyCode::KeyV, "V"), (KeyCode::KeyW, "W"), (KeyCode::KeyX, "X"),
+    (KeyCode::KeyY, "Y"), (KeyCode::KeyZ, "Z"),
+    (KeyCode::Numpad0, "Num0"), (KeyCode::Numpad1, "Num1"), (KeyCode::Numpad2, "Num2"),
+    (KeyCode::Numpad3, "Num3"), (KeyCode::Numpad4, "Num4"), (KeyCode::Numpad5, "Num5"),
+    (KeyCode::Numpad6, "Num6"), (KeyCode::Numpad7, "Num7"), (KeyCode::Numpad8, "Num8"),
+    (KeyCode::Numpad9, "Num9"),
+    (KeyCode::ArrowUp, "Up"), (KeyCode::ArrowDown, "Down"), (KeyCode::ArrowLeft, "Left"),
+    (KeyCode::ArrowRight, "Right"), (KeyCode::Space, "Space"), (KeyCode::Enter, "Enter"),
+    (KeyCode::ShiftLeft, "LShift"), (KeyCode::ShiftRight, "RShift"),
+    (KeyCode::ControlLeft, "LCtrl"), (KeyCode::ControlRight, "RCtrl"),
+    (KeyCode::Comma, ","), (KeyCode::Period, "."), (KeyCode::Slash, "/"), (KeyCode::Semicolon, ";"),
+];
+
+pub fn key_name(key: KeyCode) -> &'static str {
+    BINDABLE_KEYS.iter().find(|(bindable, _)| *bindable == key).map_or("?", |(_, name)| name)
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.iter().find(|(_, bindable)| *bindable == name).map(|(key, _)| *key)
+}
+
+/// Keypad keys in the order they are laid out on a COSMAC VIP, row by row.
+pub const KEYPAD_LAYOUT: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC,
+    0x4, 0x5, 0x6, 0xD,
+    0x7, 0x8, 0x9, 0xE,
+    0xA, 0x0, 0xB, 0xF,
+];
+
+/// The host key bound to each CHIP-8 key, indexed by key value.
+#[derive(Resource, Copy, Clone, Eq, PartialEq)]
+pub struct Keymap(pub [KeyCode; 16]);
+
+/// The same physical block of keys as the VIP keypad: 1234 / QWER / ASDF / ZXCV.
+impl Default for Keymap {
+    fn default() -> Self {
+        let host = [
+            KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+            KeyCode::KeyQ, KeyCode::KeyW, KeyCode::KeyE, KeyCode::KeyR,
+            KeyCode::KeyA, KeyCode::KeyS, KeyCode::KeyD, KeyCode::KeyF,
+            KeyCode::KeyZ, KeyCode::KeyX, KeyCode::KeyC, KeyCode::KeyV,
+        ];
+        let mut keys = [KeyCode::Digit0; 16];
+        for (chip8, host) in KEYPAD_LAYOUT.iter().zip(host) {
+            keys[*chip8 as usize] = host;
+        }
+        Self(keys)
+    }
+}
+
+impl Keymap {
+    /// Binds `host` to `chip8`, taking it away from any key it was bound to before.
+    pub fn bind(&mut self, chip8: u8, host: KeyCode) {
+        let previous = self.0[chip8 as usize];
+        for key in self.0.iter_mut() {
+            if *key == host { *key = previous; }
+        }
+        self.0[chip8 as usize] = host;
+    }
+
+    fn path(rom: &str) -> PathBuf {
+        std::path::Path::new("keymaps").join(format!("{rom}.keys"))
+    }
+
+    /// The keymap saved for `rom`, or the default layout. Keys missing from the file keep
+    /// their default binding.
+    pub fn load(rom: &str) -> Self {
+        let mut keymap = Self::default();
+        let Ok(text) = std::fs::read_to_string(Self::path(rom)) else { return keymap };
+
+        for line in text.lines() {
+            let Some((chip8, host)) = line.split_once(' ') else { continue };
+            let (Ok(chip8), Some(host)) = (u8::from_str_radix(chip8.trim(), 16), parse_key(host.trim())) else { continue };
+            if chip8 < 16 {
+                keymap.bind(chip8, host);
+            }
+        }
+        keymap
+    }
+
+    /// Writes one `<key> <host key>` line per keypad key, e.g. `A Z`.
+    pub fn save(&self, rom: &str) -> std::io::Result<()> {
+        let path = Self::path(rom);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let text: String = self.0.iter().enumerate()
+            .map(|(chip8, host)| format!("{chip8:X} {}\n", key_name(*host)))
+            .collect();
+        std::fs::write(path, text)
+    }
+}
+
+/// Gamepad buttons that can be bound to the keypad, with the names saved bindings use for them.
+pub const BINDABLE_BUTTONS: [(GamepadButton, &str); 12] = [
+    (GamepadButton::DPadUp, "Up"),
+    (GamepadButton::DPadDown, "Down"),
+    (GamepadButton::DPadLeft, "Left"),
+    (GamepadButton::DPadRight, "Right"),
+    (GamepadButton::South, "A"),
+    (GamepadButton::East, "B"),
+    (GamepadButton::West, "X"),
+    (GamepadButton::North, "Y"),
+    (GamepadButton::LeftTrigger, "LB"),
+    (GamepadButton::RightTrigger, "RB"),
+    (GamepadButton::Select, "Select"),
+    (GamepadButton::Start, "Start"),
+];
+
+/// How far the left stick has to be pushed to count as the matching d-pad direction.
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// The keypad key each of [`BINDABLE_BUTTONS`] presses, if any.
+#[derive(Resource, Copy, Clone, Eq, PartialEq)]
+pub struct ButtonMap(pub [Option<u8>; BINDABLE_BUTTONS.len()]);
+
+/// The d-pad on the 2/4/6/8 cross most games steer with, A on 5 in the middle.
+impl Default for ButtonMap {
+    fn default() -> Self {
+        Self::from_bindings(&[("Up", 0x2), ("Down", 0x8), ("Left", 0x4), ("Right", 0x6), ("A", 0x5), ("B", 0x0)])
+    }
+}
+
+impl ButtonMap {
+    fn from_bindings(bindings: &[(&str, u8)]) -> Self {
+        let mut map = Self([None; BINDABLE_BUTTONS.len()]);
+        for (button, chip8) in bindings {
+            if let Some(index) = BINDABLE_BUTTONS.iter().position(|(_, name)| name == button) {
+                map.0[index] = Some(*chip8);
+            }
+        }
+        map
+    }
+
+    /// Built-in bindings for the bundled ROMs that don't use the 2/4/6/8 cross.
+    pub fn preset(rom: &str) -> Self {
+        match rom.to_ascii_uppercase().as_str() {
+            "TETRIS" => Self::from_bindings(&[("Left", 0x5), ("Right", 0x6), ("A", 0x4), ("Up", 0x4), ("Down", 0x7)]),
+            "PONG" | "PONG2" => Self::from_bindings(&[("Up", 0x1), ("Down", 0x4), ("Y", 0xC), ("A", 0xD)]),
+            "BRIX" | "VBRIX" => Self::from_bindings(&[("Left", 0x4), ("Right", 0x6)]),
+            "INVADERS" => Self::from_bindings(&[("Left", 0x4), ("Right", 0x6), ("A", 0x5)]),
+            _ => Self::default(),
+        }
+    }
+
+    fn path(rom: &str) -> PathBuf {
+        std::path::Path::new("keymaps").join(format!("{rom}.pad"))
+    }
+
+    /// The bindings saved for `rom`, or its preset if none were saved.
+    pub fn load(rom: &str) -> Self {
+        let Ok(text) = std::fs::read_to_string(Self::path(rom)) else { return Self::preset(rom) };
+
+        let bindings: Vec<_> = text.lines()
+            .filter_map(|line| line.split_once(' '))
+            .filter_map(|(button, chip8)| Some((button.trim(), u8::from_str_radix(chip8.trim(), 16).ok().filter(|k| *k < 16)?)))
+            .collect();
+        Self::from_bindings(&bindings)
+    }
+
+    /// Writes one `<button> <key>` line per bound button, e.g. `Left 5`.
+    pub fn save(&self, rom: &str) -> std::io::Result<()> {
+        let path = Self::path(rom);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let text: String = BINDABLE_BUTTONS.iter().zip(self.0)
+            .filter_map(|((_, name), chip8)| Some(format!("{name} {:X}\n", chip8?)))
+            .collect();
+        std::fs::write(path, text)
+    }
+}
+
+pub fn input_plugin(app: &mut App) {
+    app
+        .init_resource::<Keymap>()
+        .init_resource::<ButtonMap>()
+        .add_systems(Update, (load_keymap, update_keypad).chain())
+        ;
+}
+
+/// Switches to the keymap and gamepad bindings saved for a ROM when it is loaded.
+fn load_keymap(loaded: Res<LoadedRom>, mut keymap: ResMut<Keymap>, mut buttons: ResMut<ButtonMap>) {
+    if !loaded.is_changed() { return }
+    *keymap = loaded.name().map(Keymap::load).unwrap_or_default();
+    *buttons = loaded.name().map(ButtonMap::load).unwrap_or_default();
+}
+
+/// Whether `button` is held on `gamepad`, counting the left stick as a second d-pad.
+fn button_pressed(gamepad: &Gamepad, button: GamepadButton) -> bool {
+    let stick = gamepad.left_stick();
+    gamepad.pressed(button) || match button {
+        GamepadButton::DPadUp => stick.y > STICK_THRESHOLD,
+        GamepadButton::DPadDown => stick.y < -STICK_THRESHOLD,
+        GamepadButton::DPadLeft => stick.x < -STICK_THRESHOLD,
+        GamepadButton::DPadRight => stick.x > STICK_THRESHOLD,
+        _ => false,
+    }
+}
+
+fn update_keypad(
+    keys: Res<ButtonInput<KeyCode>>,
+    keymap: Res<Keymap>,
+    buttons: Res<ButtonMap>,
+    gamepads: Query<&Gamepad>,
+    movie: Res<MovieState>,
+    mut emulator: ResMut<Emulator>,
+) {
+    // Playback sets the keys itself
+    if movie.is_playing() { return }
+
+    let mut pressed = keymap.0.map(|host| keys.pressed(host));
+    for gamepad in &gamepads {
+        for ((button, _), chip8) in BINDABLE_BUTTONS.iter().zip(buttons.0) {
+            let Some(chip8) = chip8 else { continue };
+            if button_pressed(gamepad, *button) {
+                pressed[chip8 as usize] = true;
+            }
+        }
+    }
+
+    for (chip8, pressed) in pressed.into_iter().enumerate() {
+        emulator.0.set_key(chip8 as u8, pressed);
+    }
+}
@@ -1,13 +1,17 @@
 use bevy::prelude::*;
 
+mod audio;
 mod ch8_plugin;
 mod gui;
+mod input;
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugins(gui::gui_plugin)
         .add_plugins(ch8_plugin::chip8_emulator_plugin)
+        .add_plugins(audio::audio_plugin)
+        .add_plugins(input::input_plugin)
         .add_systems(Startup, setup)
         .run();
 }
@@ -0,0 +1,317 @@
+//! Runs a CHIP-8 ROM without a window, for CI and batch testing.
+//!
+//! Usage: chip8-headless <rom> [options]
+//!
+//!   --frames N        frames to run at 60 Hz (default 600)
+//!   --ipf N           instructions per frame (default 11)
+//!   --quirks NAME     vip, chip48, schip or xochip (default vip)
+//!   --break ADDR      stop when the PC reaches ADDR (hex, repeatable), including the
+//!                     entry point
+//!   --keys SCRIPT     key events as FRAME+KEY (press) or FRAME-KEY (release), comma
+//!                     separated, e.g. `30+5,32-5`
+//!   --no-loop-stop    keep running when the program jumps to itself
+//!   --png PATH        write the final framebuffer as a PNG
+//!   --scale N         PNG pixels per CHIP-8 pixel (default 1)
+//!   --ascii PATH      write the final framebuffer as text, `-` for stdout
+//!   --json PATH       write the final registers as JSON, `-` for stdout
+//!   --trace PATH      write every executed instruction to PATH as text, faults included
+//!
+//! The exit code is 0 when the run ends normally, 1 on an emulator fault and 2 for bad
+//! arguments or files that can't be read or written.
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use chip_8::{Chip8Emulator, Chip8Error, Quirks, StepOutcome, TraceFormat, Tracer};
+
+/// Display colors indexed by the plane bits of a pixel, as in the frontend.
+const PALETTE: [[u8; 3]; 4] = [
+    [0, 0, 0],
+    [255, 255, 255],
+    [255, 170, 0],
+    [170, 85, 0],
+];
+
+struct KeyEvent {
+    frame: usize,
+    key: u8,
+    pressed: bool,
+}
+
+struct Options {
+    rom: String,
+    frames: usize,
+    instructions_per_frame: usize,
+    quirks: Quirks,
+    breakpoints: Vec<u16>,
+    keys: Vec<KeyEvent>,
+    stop_on_loop: bool,
+    png: Option<String>,
+    scale: usize,
+    ascii: Option<String>,
+    json: Option<String>,
+    trace: Option<String>,
+}
+
+fn usage(message: impl std::fmt::Display) -> ! {
+    eprintln!("{message}");
+    eprintln!("usage: chip8-headless <rom> [--frames N] [--ipf N] [--quirks NAME] [--break ADDR] [--keys SCRIPT] [--no-loop-stop] [--png PATH] [--scale N] [--ascii PATH] [--json PATH] [--trace PATH]");
+    std::process::exit(2)
+}
+
+fn parse_hex(value: &str) -> Option<u16> {
+    u16::from_str_radix(value.strip_prefix("0x").unwrap_or(value), 16).ok()
+}
+
+fn parse_keys(script: &str) -> Option<Vec<KeyEvent>> {
+    script.split(',').map(|event| {
+        let split = event.find(['+', '-'])?;
+        Some(KeyEvent {
+            frame: event[..split].trim().parse().ok()?,
+            key: u8::from_str_radix(event[split + 1..].trim(), 16).ok().filter(|key| *key < 16)?,
+            pressed: &event[split..split + 1] == "+",
+        })
+    }).collect()
+}
+
+fn parse_options() -> Options {
+    let mut args = std::env::args().skip(1);
+    let mut options = Options {
+        rom: String::new(),
+        frames: 600,
+        instructions_per_frame: 11,
+        quirks: Quirks::COSMAC_VIP,
+        breakpoints: Vec::new(),
+        keys: Vec::new(),
+        stop_on_loop: true,
+        png: None,
+        scale: 1,
+        ascii: None,
+        json: None,
+        trace: None,
+    };
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            options.rom = arg;
+            continue;
+        }
+        if arg == "--no-loop-stop" {
+            options.stop_on_loop = false;
+            continue;
+        }
+
+        let Some(value) = args.next() else { usage(format!("{arg} needs a value")) };
+        let invalid = || -> ! { usage(format!("invalid value for {arg}: {value}")) };
+        match arg.as_str() {
+            "--frames" => options.frames = value.parse().unwrap_or_else(|_| invalid()),
+            "--ipf" => options.instructions_per_frame = value.parse().unwrap_or_else(|_| invalid()),
+            "--quirks" => options.quirks = match value.as_str() {
+                "vip" => Quirks::COSMAC_VIP,
+                "chip48" => Quirks::CHIP_48,
+                "schip" => Quirks::SUPER_CHIP,
+                "xochip" => Quirks::XO_CHIP,
+                _ => invalid(),
+            },
+            "--break" => options.breakpoints.push(parse_hex(&value).unwrap_or_else(|| invalid())),
+            "--keys" => options.keys.extend(parse_keys(&value).unwrap_or_else(|| invalid())),
+            "--png" => options.png = Some(value),
+            "--scale" => options.scale = value.parse().ok().filter(|scale| *scale > 0).unwrap_or_else(|| invalid()),
+            "--ascii" => options.ascii = Some(value),
+            "--json" => options.json = Some(value),
+            "--trace" => options.trace = Some(value),
+            _ => usage(format!("unknown option {arg}")),
+        }
+    }
+
+    if options.rom.is_empty() {
+        usage("no ROM given");
+    }
+    options
+}
+
+/// Why the run ended, as reported in the JSON dump.
+enum Outcome {
+    FramesElapsed,
+    Loop,
+    Halted,
+    Breakpoint,
+    Fault(Chip8Error),
+}
+
+impl Outcome {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::FramesElapsed => "frames",
+            Self::Loop => "loop",
+            Self::Halted => "halted",
+            Self::Breakpoint => "breakpoint",
+            Self::Fault(_) => "fault",
+        }
+    }
+}
+
+/// Whether the next instruction is a jump to itself, the usual way a program ends.
+fn is_self_jump(emulator: &Chip8Emulator) -> bool {
+    let pc = emulator.registers().pc;
+    let memory = emulator.memory();
+    let Some(word) = memory.get(pc as usize..pc as usize + 2) else { return false };
+    u16::from_be_bytes([word[0], word[1]]) == 0x1000 | pc
+}
+
+fn run(emulator: &mut Chip8Emulator, options: &Options, mut tracer: Option<&mut Tracer<impl Write>>) -> (usize, Outcome) {
+    for frame in 0..options.frames {
+        for event in options.keys.iter().filter(|event| event.frame == frame) {
+            emulator.set_key(event.key, event.pressed);
+        }
+
+        // Breakpoints are checked before each instruction, so one on the entry point fires too.
+        // Timers only tick for frames that ran to the end, so a stop partway through reports
+        // them as they were at that instruction.
+        for _ in 0..options.instructions_per_frame {
+            if options.breakpoints.contains(&emulator.registers().pc) {
+                return (frame, Outcome::Breakpoint);
+            }
+            let outcome = match tracer.as_mut() {
+                Some(tracer) => tracer.tick(emulator),
+                None => emulator.tick(),
+            };
+            match outcome {
+                Err(e) => return (frame + 1, Outcome::Fault(e)),
+                Ok(StepOutcome::Halted) => return (frame + 1, Outcome::Halted),
+                Ok(StepOutcome::WaitingForKey) => break,
+                Ok(StepOutcome::Executed) => {}
+            }
+        }
+        emulator.tick_timers();
+        if options.stop_on_loop && is_self_jump(emulator) {
+            return (frame + 1, Outcome::Loop);
+        }
+    }
+    (options.frames, Outcome::FramesElapsed)
+}
+
+fn write_png(emulator: &Chip8Emulator, path: &str, scale: usize) -> Result<(), String> {
+    let (width, height) = (emulator.display_width(), emulator.display_height());
+    let mut data = Vec::with_capacity(width * height * scale * scale * 3);
+    for row in emulator.framebuffer().rows() {
+        for _ in 0..scale {
+            for pixel in row.pixels() {
+                for _ in 0..scale {
+                    data.extend_from_slice(&PALETTE[pixel as usize]);
+                }
+            }
+        }
+    }
+
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), (width * scale) as u32, (height * scale) as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&data).map_err(|e| e.to_string())
+}
+
+fn json(emulator: &Chip8Emulator, frames: usize, outcome: &Outcome) -> String {
+    let registers = emulator.registers();
+    let list = |values: &mut dyn Iterator<Item = u16>| values.map(|v| v.to_string()).collect::<Vec<_>>().join(", ");
+
+    let mut out = String::new();
+    writeln!(out, "{{").unwrap();
+    writeln!(out, "  \"stop\": \"{}\",", outcome.name()).unwrap();
+    if let Outcome::Fault(e) = outcome {
+        writeln!(out, "  \"fault\": \"{}\",", escape(&e.to_string())).unwrap();
+    }
+    writeln!(out, "  \"frames\": {frames},").unwrap();
+    writeln!(out, "  \"cycles\": {},", emulator.cycles()).unwrap();
+    writeln!(out, "  \"pc\": {},", registers.pc).unwrap();
+    writeln!(out, "  \"i\": {},", registers.i).unwrap();
+    writeln!(out, "  \"v\": [{}],", list(&mut registers.v.iter().map(|v| *v as u16))).unwrap();
+    writeln!(out, "  \"sp\": {},", registers.sp).unwrap();
+    writeln!(out, "  \"stack\": [{}],", list(&mut emulator.stack().iter().copied())).unwrap();
+    writeln!(out, "  \"delay\": {},", registers.delay).unwrap();
+    writeln!(out, "  \"sound\": {}", registers.sound).unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+/// `text` with the characters JSON strings can't hold as they are escaped.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Writes `text` to `path`, or to stdout for `-`.
+fn write_text(path: &str, text: &str) -> Result<(), String> {
+    match path {
+        "-" => std::io::stdout().lock().write_all(text.as_bytes()),
+        _ => std::fs::write(path, text),
+    }.map_err(|e| e.to_string())
+}
+
+fn main() {
+    let options = parse_options();
+
+    let program = match std::fs::read(&options.rom) {
+        Ok(v) => v,
+        Err(e) => { eprintln!("could not read {}: {e}", options.rom); std::process::exit(2) },
+    };
+    let mut emulator = Chip8Emulator::default();
+    emulator.set_quirks(options.quirks);
+    if let Err(e) = emulator.load_rom(&program) {
+        eprintln!("could not load {}: {e}", options.rom);
+        std::process::exit(2);
+    }
+
+    let mut tracer = match &options.trace {
+        None => None,
+        Some(path) => match File::create(path).map_err(Chip8Error::from).and_then(|file| Tracer::new(BufWriter::new(file), TraceFormat::Text)) {
+            Ok(tracer) => Some(tracer),
+            Err(e) => { eprintln!("could not write {path}: {e}"); std::process::exit(2) },
+        },
+    };
+
+    let (frames, outcome) = run(&mut emulator, &options, tracer.as_mut());
+    let pc = emulator.registers().pc;
+    match &outcome {
+        Outcome::Fault(e) => eprintln!("fault after {frames} frames: {e}"),
+        _ => eprintln!("stopped after {frames} frames ({}) at 0x{pc:03X}", outcome.name()),
+    }
+
+    let mut results = Vec::new();
+    if let (Some(path), Some(tracer)) = (&options.trace, &mut tracer) {
+        results.push((path, tracer.flush().map_err(|e| e.to_string())));
+    }
+    if let Some(path) = &options.png {
+        results.push((path, write_png(&emulator, path, options.scale)));
+    }
+    if let Some(path) = &options.ascii {
+        results.push((path, write_text(path, &emulator.framebuffer().to_string())));
+    }
+    if let Some(path) = &options.json {
+        results.push((path, write_text(path, &json(&emulator, frames, &outcome))));
+    }
+
+    // The emulator itself never does I/O, so such an error means the trace couldn't be written
+    let mut exit_code = match outcome {
+        Outcome::Fault(Chip8Error::Io(_)) => 2,
+        Outcome::Fault(_) => 1,
+        _ => 0,
+    };
+    for (path, result) in results {
+        if let Err(e) = result {
+            eprintln!("could not write {path}: {e}");
+            exit_code = exit_code.max(2);
+        }
+    }
+    std::process::exit(exit_code);
+}
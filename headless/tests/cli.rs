@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Writes `program` to a temporary file named after the test.
+fn rom(name: &str, program: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("chip8-headless-{name}-{}.ch8", std::process::id()));
+    std::fs::write(&path, program).unwrap();
+    path
+}
+
+/// Runs the binary on `rom` with the registers dumped to stdout.
+fn run(rom: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chip8-headless"))
+        .arg(rom)
+        .args(["--json", "-"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn stops_on_self_jump() {
+    // v0 := 1; jump 0x202
+    let output = run(&rom("loop", &[0x60, 0x01, 0x12, 0x02]), &[]);
+    assert_eq!(output.status.code(), Some(0));
+    let json = stdout(&output);
+    assert!(json.contains("\"stop\": \"loop\""), "{json}");
+    assert!(json.contains("\"frames\": 1,"), "{json}");
+    assert!(json.contains("\"pc\": 514,"), "{json}");
+}
+
+#[test]
+fn faults_exit_with_1() {
+    // return
+    let output = run(&rom("fault", &[0x00, 0xEE]), &[]);
+    assert_eq!(output.status.code(), Some(1));
+    let json = stdout(&output);
+    assert!(json.contains("\"fault\": \"stack underflow at 0x200\""), "{json}");
+}
+
+#[test]
+fn trace_ends_with_the_fault() {
+    // v0 := 1; return
+    let path = rom("trace", &[0x60, 0x01, 0x00, 0xEE]);
+    let trace = std::env::temp_dir().join(format!("chip8-headless-trace-{}.txt", std::process::id()));
+    let output = run(&path, &["--trace", trace.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let text = std::fs::read_to_string(&trace).unwrap();
+    assert_eq!(text.lines().collect::<Vec<_>>(), [
+        "       1 0200 6001 LD V0, 0x01          V0=01",
+        "       2 0202 00EE RET                  FAULT",
+    ]);
+}
+
+#[test]
+fn bad_arguments_exit_with_2() {
+    let path = rom("arguments", &[0x12, 0x00]);
+    for args in [
+        &["--break", "0x0x200"][..],
+        &["--break", "0x10000"],
+        &["--keys", "30+5,x-5"],
+        &["--keys", "30+10"],
+        &["--keys", "30*5"],
+        &["--frames", "-1"],
+        &["--quirks", "cosmac"],
+        &["--unknown", "1"],
+        &["--ipf"],
+    ] {
+        assert_eq!(run(&path, args).status.code(), Some(2), "{args:?}");
+    }
+
+    let missing = std::env::temp_dir().join("chip8-headless-missing.ch8");
+    assert_eq!(run(&missing, &[]).status.code(), Some(2));
+}
+
+#[test]
+fn breakpoints_stop_the_run() {
+    // v0 := 1; v1 := 2; jump 0x204
+    let path = rom("break", &[0x60, 0x01, 0x61, 0x02, 0x12, 0x04]);
+
+    let json = stdout(&run(&path, &["--break", "0x202"]));
+    assert!(json.contains("\"stop\": \"breakpoint\""), "{json}");
+    assert!(json.contains("\"pc\": 514,"), "{json}");
+    assert!(json.contains("\"v\": [1, 0,"), "{json}");
+
+    // The entry point stops before anything runs, hex with or without a prefix
+    let json = stdout(&run(&path, &["--break", "200"]));
+    assert!(json.contains("\"stop\": \"breakpoint\""), "{json}");
+    assert!(json.contains("\"frames\": 0,"), "{json}");
+    assert!(json.contains("\"cycles\": 0,"), "{json}");
+}
+
+#[test]
+fn stopping_mid_frame_leaves_timers() {
+    // v0 := 5; delay := v0; v1 := 1; jump 0x206
+    let path = rom("timers", &[0x60, 0x05, 0xF0, 0x15, 0x61, 0x01, 0x12, 0x06]);
+
+    let json = stdout(&run(&path, &["--break", "0x204"]));
+    assert!(json.contains("\"stop\": \"breakpoint\""), "{json}");
+    assert!(json.contains("\"delay\": 5,"), "{json}");
+
+    // A frame that runs to the end ticks them once
+    let json = stdout(&run(&path, &[]));
+    assert!(json.contains("\"stop\": \"loop\""), "{json}");
+    assert!(json.contains("\"delay\": 4,"), "{json}");
+}
+
+#[test]
+fn key_script_presses_and_releases() {
+    // v0 := key; jump 0x202
+    let path = rom("keys", &[0xF0, 0x0A, 0x12, 0x02]);
+
+    let json = stdout(&run(&path, &["--keys", "3+a, 5-a"]));
+    assert!(json.contains("\"stop\": \"loop\""), "{json}");
+    assert!(json.contains("\"v\": [10, 0,"), "{json}");
+
+    // Without any key the program is still waiting when the frames run out
+    let json = stdout(&run(&path, &["--frames", "10"]));
+    assert!(json.contains("\"stop\": \"frames\""), "{json}");
+    assert!(json.contains("\"pc\": 512,"), "{json}");
+}